@@ -0,0 +1,79 @@
+//! Lander against terrain, the workload of headless and batch runs, and
+//! a scene full of settled debris.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fma::{
+    add_terrain, collided, create_lander, generate_terrain_with, partition_terrain, pos,
+    ConvexBody, Engine, MOON_GRAVITY,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+fn lander_vs_terrain(c: &mut Criterion) {
+    let terrain = generate_terrain_with(&mut StdRng::seed_from_u64(7));
+    let partitions = partition_terrain(&terrain);
+    // Low enough for the partitions under it to be close calls.
+    let lander = [
+        pos(49.0, 22.0),
+        pos(51.0, 22.0),
+        pos(51.0, 20.0),
+        pos(49.0, 20.0),
+    ];
+
+    c.bench_function("collided lander vs terrain", |b| {
+        b.iter(|| {
+            partitions
+                .iter()
+                .filter(|p| collided(black_box(&lander), black_box(&p.mesh)))
+                .count()
+        })
+    });
+
+    c.bench_function("engine tick lander over terrain", |b| {
+        b.iter_batched(
+            || {
+                let mut engine = Engine::create(MOON_GRAVITY);
+                engine.add_body(create_lander());
+                add_terrain(&mut engine, &partitions);
+                engine
+            },
+            |mut engine| {
+                for _ in 0..60 {
+                    engine.tick(black_box(1.0 / 60.0));
+                }
+                engine
+            },
+            criterion::BatchSize::SmallInput,
+        )
+    });
+}
+
+fn settled_debris(c: &mut Criterion) {
+    // Pairs of overlapping pieces, still in zero gravity, fall asleep.
+    let mut engine = Engine::create(0.0);
+    for i in 0..500 {
+        let (x, y) = ((i % 25) as f64 * 4.0, (i / 25) as f64 * 4.0);
+        for offset in [0.0, 0.5] {
+            engine.add_body(ConvexBody::still_body(
+                1.0,
+                &[
+                    pos(x + offset, y + offset),
+                    pos(x + offset + 1.0, y + offset),
+                    pos(x + offset + 1.0, y + offset + 1.0),
+                    pos(x + offset, y + offset + 1.0),
+                ],
+            ));
+        }
+    }
+    for _ in 0..60 {
+        engine.tick(1.0 / 60.0);
+    }
+    assert!(engine.bodies().all(|(_, body)| body.sleeping));
+
+    c.bench_function("engine tick over settled debris", |b| {
+        b.iter(|| engine.tick(black_box(1.0 / 60.0)))
+    });
+}
+
+criterion_group!(benches, lander_vs_terrain, settled_debris);
+criterion_main!(benches);
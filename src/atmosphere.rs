@@ -0,0 +1,213 @@
+//! Atmosphere for bodies other than the Moon: air density falling off
+//! exponentially with altitude, quadratic drag and wind.
+
+use crate::{project, v, ConvexBody, Vector};
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Wind {
+    Constant(Vector),
+    /// `mean` wind with gusts along it of up to `amplitude` m/s, coming
+    /// back roughly every `period` seconds.
+    Gusty {
+        mean: Vector,
+        amplitude: f64,
+        period: f64,
+    },
+    /// Wind growing linearly from `surface` at altitude 0 to `aloft`
+    /// at `altitude`, and constant above.
+    Shear {
+        surface: Vector,
+        aloft: Vector,
+        altitude: f64,
+    },
+}
+
+impl Wind {
+    /// Wind velocity at `altitude`, `time` seconds into the simulation.
+    pub fn at(&self, altitude: f64, time: f64) -> Vector {
+        match self {
+            Wind::Constant(wind) => *wind,
+            Wind::Gusty {
+                mean,
+                amplitude,
+                period,
+            } => {
+                // Two incommensurate waves, so gusts don't repeat exactly.
+                let phase = 2.0 * PI * time / period;
+                let gust = 0.25 * amplitude * (2.0 + phase.sin() + (2.0_f64.sqrt() * phase).sin());
+                let speed = mean.length();
+                if speed > 0.0 {
+                    *mean * (1.0 + gust / speed)
+                } else {
+                    Vector::ZERO
+                }
+            }
+            Wind::Shear {
+                surface,
+                aloft,
+                altitude: top,
+            } => {
+                let t = (altitude / top).clamp(0.0, 1.0);
+                *surface + (*aloft - *surface) * t
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Atmosphere {
+    /// Air density at altitude 0, in kg/m³.
+    pub surface_density: f64,
+    /// Altitude over which density drops by a factor e, in meters.
+    pub scale_height: f64,
+    /// Drag coefficient of every body.
+    pub drag_coefficient: f64,
+    pub wind: Wind,
+}
+
+impl Atmosphere {
+    pub fn mars() -> Atmosphere {
+        Atmosphere {
+            surface_density: 0.020,
+            scale_height: 11_100.0,
+            drag_coefficient: 1.0,
+            wind: Wind::Constant(v(0.0, 0.0)),
+        }
+    }
+
+    pub fn earth() -> Atmosphere {
+        Atmosphere {
+            surface_density: 1.225,
+            scale_height: 8_500.0,
+            drag_coefficient: 1.0,
+            wind: Wind::Constant(v(0.0, 0.0)),
+        }
+    }
+
+    pub fn titan() -> Atmosphere {
+        Atmosphere {
+            surface_density: 5.3,
+            scale_height: 40_000.0,
+            drag_coefficient: 1.0,
+            wind: Wind::Constant(v(0.0, 0.0)),
+        }
+    }
+
+    pub fn with_wind(mut self, wind: Wind) -> Atmosphere {
+        self.wind = wind;
+        self
+    }
+
+    pub fn density(&self, altitude: f64) -> f64 {
+        self.surface_density * (-altitude.max(0.0) / self.scale_height).exp()
+    }
+
+    /// Acceleration drag gives `body`, `time` seconds into the simulation.
+    /// The cross-section is the width of the body seen from the direction
+    /// of the airflow, per meter of depth. Drag is limited to what stops
+    /// the body relative to the air in `dt` seconds, so light bodies
+    /// don't end up flying against the wind.
+    pub fn drag(&self, body: &ConvexBody, time: f64, dt: f64) -> Vector {
+        if body.fixed || body.shape().is_empty() {
+            return v(0.0, 0.0);
+        }
+        let altitude = body.position().y;
+        let wind = self.wind.at(altitude, time);
+        let airflow = body.velocity - wind;
+        let speed = airflow.length();
+        if speed == 0.0 {
+            return Vector::ZERO;
+        }
+        let axis = (airflow / speed).perpendicular();
+        let (min, max) = body
+            .primitives()
+            .fold((f64::MAX, f64::MIN), |(min, max), primitive| {
+                let (low, high) = project(primitive.core(), &axis);
+                (
+                    min.min(low - primitive.radius()),
+                    max.max(high + primitive.radius()),
+                )
+            });
+        let cross_section = max - min;
+        let deceleration =
+            (0.5 * self.density(altitude) * self.drag_coefficient * cross_section * speed * speed
+                / body.mass)
+                .min(speed / dt);
+        -airflow / speed * deceleration
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::atmosphere::*;
+    use crate::{pos, square};
+
+    fn moving_square(velocity: Vector) -> ConvexBody {
+        ConvexBody {
+            velocity,
+            ..square(10.0, pos(1.0, 1.0), 2.0)
+        }
+    }
+
+    #[test]
+    fn density_falls_off_with_altitude() {
+        let mars = Atmosphere::mars();
+        assert_eq!(mars.density(0.0), 0.020);
+        assert!((mars.density(mars.scale_height) - 0.020 / std::f64::consts::E).abs() < 1e-12);
+        assert_eq!(mars.density(-5.0), 0.020);
+    }
+
+    #[test]
+    fn drag_opposes_airflow() {
+        let titan = Atmosphere::titan();
+        let drag = titan.drag(&moving_square(v(0.0, -2.0)), 0.0, 0.01);
+        // 0.5 * ρ * Cd * 2 m * (2 m/s)² / 10 kg, at 1 m of altitude.
+        let expected = 0.5 * titan.density(1.0) * 2.0 * 4.0 / 10.0;
+        assert_eq!(drag.x, 0.0);
+        assert!((drag.y - expected).abs() < 1e-12);
+
+        // Wind blowing as fast as the body moves cancels drag.
+        let calm = titan.with_wind(Wind::Constant(v(3.0, 0.0)));
+        assert_eq!(
+            calm.drag(&moving_square(v(3.0, 0.0)), 0.0, 1.0),
+            v(0.0, 0.0)
+        );
+        // A diagonal airflow sees the square's diagonal.
+        let drag = calm.drag(&moving_square(v(4.0, 1.0)), 0.0, 1.0);
+        assert!(drag.x < 0.0 && drag.y < 0.0);
+        assert!((drag.x - drag.y).abs() < 1e-12);
+    }
+
+    #[test]
+    fn drag_never_reverses_airflow() {
+        let thick = Atmosphere {
+            surface_density: 1000.0,
+            ..Atmosphere::titan()
+        };
+        let drag = thick.drag(&moving_square(v(5.0, 0.0)), 0.0, 0.1);
+        assert!((drag.x + 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wind_models() {
+        let shear = Wind::Shear {
+            surface: v(0.0, 0.0),
+            aloft: v(10.0, 0.0),
+            altitude: 100.0,
+        };
+        assert_eq!(shear.at(50.0, 0.0), v(5.0, 0.0));
+        assert_eq!(shear.at(500.0, 0.0), v(10.0, 0.0));
+
+        let gusty = Wind::Gusty {
+            mean: v(-4.0, 0.0),
+            amplitude: 2.0,
+            period: 5.0,
+        };
+        let speeds = (0..1000)
+            .map(|i| -gusty.at(0.0, i as f64 * 0.1).x)
+            .collect::<Vec<_>>();
+        assert!(speeds.iter().all(|&s| (4.0..=6.0).contains(&s)));
+        assert!(speeds.iter().any(|&s| s > 5.5));
+    }
+}
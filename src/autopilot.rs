@@ -0,0 +1,272 @@
+//! Guidance for landing the lander on its own. It flies over the landing
+//! pad at a safe altitude, then descends following a suicide burn profile:
+//! free fall for as long as the engine can still brake in time, which
+//! keeps the fuel spent close to the minimum.
+
+use crate::gear::{follow_touchdown, Touchdown};
+use crate::{
+    apply_thrust, bounds, BodyId, ConvexBody, Engine, TerrainPartition, Thrust, Vector,
+    LATERAL_THRUST, MAIN_ENGINE_THRUST,
+};
+
+/// Altitude kept over the highest terrain point while flying towards
+/// the pad, in meters.
+const CLEARANCE: f64 = 5.0;
+/// Vertical speed kept for the last meters before touchdown.
+const TOUCHDOWN_SPEED: f64 = 0.3;
+/// Fraction of the engine braking capacity the descent profile relies on,
+/// leaving the rest to correct for errors.
+const BRAKING_MARGIN: f64 = 0.5;
+/// Maximum horizontal speed while flying towards the pad.
+const CRUISE_SPEED: f64 = 5.0;
+/// How far from the pad center, and how fast horizontally, the lander
+/// can be when it starts descending onto the pad.
+const POSITION_TOLERANCE: f64 = 0.5;
+const SPEED_TOLERANCE: f64 = 0.2;
+
+/// Proportional gains of the position and velocity loops.
+const POSITION_GAIN: f64 = 0.5;
+const VELOCITY_GAIN: f64 = 2.0;
+
+/// Flat terrain where the lander should land.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pad {
+    pub left: f64,
+    pub right: f64,
+    pub height: f64,
+}
+
+impl Pad {
+    pub fn center(&self) -> f64 {
+        (self.left + self.right) / 2.0
+    }
+}
+
+/// Returns the safe partition of `terrain` closest to `x`, if there is any.
+pub fn find_pad(terrain: &[TerrainPartition], x: f64) -> Option<Pad> {
+    terrain
+        .iter()
+        .filter(|p| p.safe)
+        .map(|p| Pad {
+            left: p.mesh[0].x,
+            right: p.mesh[1].x,
+            height: p.mesh[0].y,
+        })
+        .min_by(|p1, p2| (p1.center() - x).abs().total_cmp(&(p2.center() - x).abs()))
+}
+
+#[derive(Debug)]
+pub struct Autopilot {
+    pad: Pad,
+    /// Altitude to fly at when not over the pad.
+    safe_altitude: f64,
+    /// Whether the lander is committed to the final descent.
+    descending: bool,
+    /// Main engine usage so far, in seconds at full throttle.
+    fuel_used: f64,
+}
+
+impl Autopilot {
+    /// Creates an autopilot targeting the safe partition of `terrain`
+    /// closest to `x`, if there is any.
+    pub fn create(terrain: &[TerrainPartition], x: f64) -> Option<Autopilot> {
+        let pad = find_pad(terrain, x)?;
+        let highest = terrain
+            .iter()
+            .flat_map(|p| [p.mesh[0].y, p.mesh[1].y])
+            .fold(f64::MIN, f64::max);
+        Some(Autopilot {
+            pad,
+            safe_altitude: highest + CLEARANCE,
+            descending: false,
+            fuel_used: 0.0,
+        })
+    }
+
+    pub fn pad(&self) -> &Pad {
+        &self.pad
+    }
+
+    pub fn fuel_used(&self) -> f64 {
+        self.fuel_used
+    }
+
+    /// Computes the thrust to apply on `lander`, under gravity `ga`,
+    /// for the next `dt` seconds.
+    pub fn command(&mut self, lander: &ConvexBody, ga: f64, dt: f64) -> Thrust {
+        let (min, max) = bounds(lander.mesh());
+        let x = (min.x + max.x) / 2.0;
+        self.guide(x, min.y, &lander.velocity, lander.mass, ga, dt)
+    }
+
+    /// Same as `command`, for a lander centered at `x`, with its lowest
+    /// point at `bottom`, moving at `velocity`.
+    pub fn guide(
+        &mut self,
+        x: f64,
+        bottom: f64,
+        velocity: &Vector,
+        mass: f64,
+        ga: f64,
+        dt: f64,
+    ) -> Thrust {
+        let main_acceleration = MAIN_ENGINE_THRUST / mass;
+        let lateral_acceleration = LATERAL_THRUST / mass;
+
+        let error = self.pad.center() - x;
+        if error.abs() < POSITION_TOLERANCE && velocity.x.abs() < SPEED_TOLERANCE {
+            self.descending = true;
+        } else if error.abs() > (self.pad.right - self.pad.left) / 2.0 {
+            self.descending = false;
+        }
+
+        let target_vx = (error * POSITION_GAIN).clamp(-CRUISE_SPEED, CRUISE_SPEED);
+        let lateral = (target_vx - velocity.x) * VELOCITY_GAIN / lateral_acceleration;
+
+        let floor = if self.descending {
+            self.pad.height
+        } else {
+            self.safe_altitude
+        };
+        let altitude = bottom - floor;
+        // Braking at a constant deceleration, speed goes down with the
+        // square root of the distance left. Following that profile needs
+        // the rate at which it changes, at the current speed, as feed forward.
+        let braking = BRAKING_MARGIN * (main_acceleration - ga).max(0.0);
+        let profile = (2.0 * braking * altitude.max(0.0)).sqrt();
+        let (target_vy, feed_forward) = if altitude <= 0.0 {
+            ((-altitude * POSITION_GAIN).min(CRUISE_SPEED), 0.0)
+        } else if self.descending && profile < TOUCHDOWN_SPEED {
+            (-TOUCHDOWN_SPEED, 0.0)
+        } else {
+            let rate = if profile > 0.0 {
+                braking * (-velocity.y).max(0.0) / profile
+            } else {
+                0.0
+            };
+            (-profile, rate)
+        };
+        let main =
+            (ga + feed_forward + (target_vy - velocity.y) * VELOCITY_GAIN) / main_acceleration;
+
+        let thrust = Thrust {
+            main: main.clamp(0.0, 1.0),
+            lateral: lateral.clamp(-1.0, 1.0),
+        };
+        self.fuel_used += thrust.main * dt;
+        thrust
+    }
+}
+
+/// Flies `lander` headless with `autopilot`, in steps of `dt` seconds,
+/// cutting the engine at the first contact and following the touchdown
+/// on the legs until the landing is decided. `terrain` tells which bodies
+/// are safe to land on. Returns the flight time until the first contact
+/// and the touchdown, or `None` if the landing wasn't decided within
+/// `timeout` seconds or there is no `lander` in `engine`.
+pub fn fly(
+    engine: &mut Engine,
+    lander: BodyId,
+    autopilot: &mut Autopilot,
+    terrain: &[(BodyId, bool)],
+    dt: f64,
+    timeout: f64,
+) -> Option<(f64, Touchdown)> {
+    let mut time = 0.0;
+    let mut flight = 0.0;
+    let mut touchdown = None;
+    while time < timeout {
+        if touchdown.is_none() {
+            let ga = engine
+                .gravity
+                .strength_at(&engine.get_body(lander)?.position());
+            let body = engine.get_body_mut(lander)?;
+            let thrust = autopilot.command(body, ga, dt);
+            apply_thrust(body, &thrust);
+        }
+        engine.tick(dt);
+        time += dt;
+        let flying = touchdown.is_none();
+        let landing = follow_touchdown(&mut touchdown, engine, lander, terrain, dt);
+        if flying && touchdown.is_some() {
+            flight = time;
+            engine.get_body_mut(lander)?.set_resulting_force(0.0, 0.0);
+        }
+        if landing.is_some() {
+            return Some((flight, touchdown?));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use crate::autopilot::*;
+    use crate::{add_terrain, create_lander, partition_terrain, Landing, MOON_GRAVITY};
+
+    /// Drops the lander from its starting point and lets the autopilot
+    /// land it.
+    fn land(terrain: &[crate::Position]) -> (Landing, Autopilot) {
+        let partitions = partition_terrain(terrain);
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let lander = engine.add_body(create_lander());
+        let terrain_safety = add_terrain(&mut engine, &partitions);
+        let mut autopilot = Autopilot::create(&partitions, 50.0).unwrap();
+
+        let (_, touchdown) = fly(
+            &mut engine,
+            lander,
+            &mut autopilot,
+            &terrain_safety,
+            1.0 / 60.0,
+            300.0,
+        )
+        .unwrap();
+        (touchdown.landing().unwrap(), autopilot)
+    }
+
+    #[test]
+    fn picks_closest_pad() {
+        let terrain = positions![
+            (0.0, 5.0),
+            (10.0, 5.0),
+            (20.0, 8.0),
+            (30.0, 8.0),
+            (40.0, 19.0)
+        ];
+        let partitions = partition_terrain(&terrain);
+        let autopilot = Autopilot::create(&partitions, 40.0).unwrap();
+        assert_eq!(
+            autopilot.pad(),
+            &Pad {
+                left: 20.0,
+                right: 30.0,
+                height: 8.0
+            }
+        );
+        assert_eq!(autopilot.safe_altitude, 19.0 + CLEARANCE);
+        assert!(Autopilot::create(&partition_terrain(&terrain[1..3]), 0.0).is_none());
+    }
+
+    #[test]
+    fn lands_below_starting_point() {
+        let terrain = positions![(40.0, 15.0), (45.0, 3.0), (50.0, 3.0), (55.0, 18.0)];
+        let (landing, autopilot) = land(&terrain);
+        assert_eq!(landing, Landing::Safe);
+        assert!(autopilot.fuel_used() > 0.0);
+    }
+
+    #[test]
+    fn lands_far_away_over_tall_terrain() {
+        let terrain = positions![
+            (0.0, 10.0),
+            (5.0, 12.0),
+            (10.0, 12.0),
+            (15.0, 19.0),
+            (50.0, 19.5),
+            (95.0, 4.0),
+            (100.0, 6.0)
+        ];
+        assert_eq!(land(&terrain).0, Landing::Safe);
+    }
+}
@@ -0,0 +1,220 @@
+//! Steps many independent lander worlds in lockstep. Each world is a
+//! `LanderEnv` with the same config, so it flies and lands exactly as a
+//! single environment would. Worlds are stepped without being observed,
+//! observations are made only when asked for. With the `parallel`
+//! feature, worlds are stepped on all cores.
+
+use crate::env::{EnvConfig, LanderEnv, Outcome};
+use crate::Thrust;
+
+#[cfg(not(feature = "parallel"))]
+use itertools::izip;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldOutcome {
+    pub outcome: Outcome,
+    /// Simulated seconds until the world ended.
+    pub time: f64,
+    /// Seconds of main engine at full thrust.
+    pub fuel_used: f64,
+}
+
+pub struct BatchSim {
+    worlds: Vec<LanderEnv>,
+    outcomes: Vec<Option<WorldOutcome>>,
+}
+
+/// Steps one world by one step of its config. Finished worlds are left
+/// untouched.
+fn step_world(world: &mut LanderEnv, outcome: &mut Option<WorldOutcome>, action: &Thrust) {
+    if outcome.is_some() {
+        return;
+    }
+    world.advance(action);
+    let info = world.info();
+    *outcome = info.outcome.map(|outcome| WorldOutcome {
+        outcome,
+        time: info.time,
+        fuel_used: info.fuel_used,
+    });
+}
+
+impl BatchSim {
+    /// Creates one world per seed, each as `LanderEnv` would start it
+    /// from the same seed under `config`.
+    pub fn create(seeds: &[u64], config: EnvConfig) -> BatchSim {
+        let worlds = seeds
+            .iter()
+            .map(|&seed| LanderEnv::create_with_seed(config.clone(), seed))
+            .collect();
+        BatchSim {
+            worlds,
+            outcomes: vec![None; seeds.len()],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.worlds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.worlds.is_empty()
+    }
+
+    pub fn world(&self, world: usize) -> &LanderEnv {
+        &self.worlds[world]
+    }
+
+    /// Whether every world has ended.
+    pub fn finished(&self) -> bool {
+        self.outcomes.iter().all(Option::is_some)
+    }
+
+    pub fn outcomes(&self) -> &[Option<WorldOutcome>] {
+        &self.outcomes
+    }
+
+    /// Puts the observation vectors of all worlds one after the other in
+    /// `observations`, replacing what it held.
+    pub fn observe(&self, observations: &mut Vec<f64>) {
+        observations.clear();
+        self.worlds
+            .iter()
+            .for_each(|world| world.observe_into(observations));
+    }
+
+    /// Steps every world by one step, applying `actions[i]` to world `i`.
+    pub fn step(&mut self, actions: &[Thrust]) {
+        assert_eq!(actions.len(), self.len());
+
+        #[cfg(not(feature = "parallel"))]
+        izip!(&mut self.worlds, &mut self.outcomes, actions)
+            .for_each(|(world, outcome, action)| step_world(world, outcome, action));
+
+        #[cfg(feature = "parallel")]
+        (
+            self.worlds.par_iter_mut(),
+            self.outcomes.par_iter_mut(),
+            actions.par_iter(),
+        )
+            .into_par_iter()
+            .for_each(|(world, outcome, action)| step_world(world, outcome, action));
+    }
+
+    /// Steps all worlds until they end, which they do at the latest at
+    /// the time limit of their config, asking `policy` for the action of
+    /// each world on every step.
+    pub fn run<P>(&mut self, mut policy: P) -> Vec<WorldOutcome>
+    where
+        P: FnMut(usize, &LanderEnv) -> Thrust,
+    {
+        let mut actions = vec![Thrust::default(); self.len()];
+        while !self.finished() {
+            actions.iter_mut().enumerate().for_each(|(world, action)| {
+                if self.outcomes[world].is_none() {
+                    *action = policy(world, &self.worlds[world]);
+                }
+            });
+            self.step(&actions);
+        }
+        self.outcomes.iter().flatten().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::autopilot::Autopilot;
+    use crate::batch::*;
+    use crate::gravity::Planet;
+    use crate::partition_terrain;
+
+    #[test]
+    fn worlds_match_env() {
+        let seeds: Vec<u64> = (0..8).collect();
+        let config = EnvConfig::for_planet(Planet::Mars);
+        let action = |seed: u64| Thrust {
+            main: 0.2 + 0.05 * seed as f64,
+            lateral: 0.1,
+        };
+        let mut batch = BatchSim::create(&seeds, config.clone());
+        let outcomes = batch.run(|world, _| action(seeds[world]));
+
+        let mut observations = vec![];
+        batch.observe(&mut observations);
+        let mut env = LanderEnv::create(config);
+        assert_eq!(observations.len(), seeds.len() * env.observation_len());
+        let observations = observations.chunks(env.observation_len());
+        seeds
+            .iter()
+            .zip(outcomes)
+            .zip(observations)
+            .for_each(|((&seed, outcome), observation)| {
+                env.reset(seed);
+                let info = loop {
+                    let (_, _, done, info) = env.step(&action(seed));
+                    if done {
+                        break info;
+                    }
+                };
+                assert_eq!(Some(outcome.outcome), info.outcome);
+                assert_eq!(outcome.time, info.time);
+                assert_eq!(outcome.fuel_used, info.fuel_used);
+                let world = batch.world(seed as usize).lander();
+                assert_eq!(world.position(), env.lander().position());
+                assert_eq!(world.velocity, env.lander().velocity);
+                assert_eq!(observation, env.observe());
+            });
+    }
+
+    #[test]
+    fn autopilot_lands_every_world() {
+        let seeds: Vec<u64> = (0..16).collect();
+        let config = EnvConfig::default();
+        let dt = config.dt;
+        let mut batch = BatchSim::create(&seeds, config);
+        let mut autopilots: Vec<Autopilot> = (0..batch.len())
+            .map(|world| {
+                let partitions = partition_terrain(batch.world(world).terrain());
+                Autopilot::create(&partitions, 50.0).unwrap()
+            })
+            .collect();
+
+        let outcomes =
+            batch.run(|world, env| autopilots[world].command(env.lander(), env.gravity(), dt));
+
+        assert!(batch.finished());
+        assert!(outcomes.iter().all(|o| o.outcome == Outcome::Landed));
+        assert!(outcomes.iter().all(|o| o.fuel_used > 0.0));
+    }
+
+    #[test]
+    fn finished_worlds_stay_still() {
+        let config = EnvConfig {
+            time_limit: 2.0,
+            ..EnvConfig::default()
+        };
+        let mut batch = BatchSim::create(&[0, 1], config);
+        let full_thrust = Thrust {
+            main: 1.0,
+            lateral: 0.0,
+        };
+        let outcomes = batch.run(|world, _| {
+            if world == 0 {
+                Thrust::default()
+            } else {
+                full_thrust.clone()
+            }
+        });
+        assert_eq!(outcomes[0].outcome, Outcome::TimeOut);
+        assert_eq!(outcomes[1].outcome, Outcome::TimeOut);
+
+        let before = [0, 1].map(|world| batch.world(world).lander().position());
+        batch.step(&[Thrust::default(), full_thrust]);
+        assert_eq!(
+            before,
+            [0, 1].map(|world| batch.world(world).lander().position())
+        );
+    }
+}
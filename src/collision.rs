@@ -0,0 +1,219 @@
+//! Collision tests between the primitives bodies are made of. Circles,
+//! capsules and segments are points and segments grown by a radius, so
+//! they're all tested with SAT: the edge normals of both cores are tried
+//! as axes, plus the directions between their vertices, which is where
+//! rounded ends face each other.
+
+use crate::{collided, project, ConvexBody, Position};
+use itertools::Itertools;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Primitive<'a> {
+    /// Convex polygon, as `collided` takes it.
+    Polygon(&'a [Position]),
+    Circle {
+        center: Position,
+        radius: f64,
+    },
+    /// Points within `radius` of the segment between `ends`.
+    Capsule {
+        ends: [Position; 2],
+        radius: f64,
+    },
+    Segment {
+        ends: [Position; 2],
+    },
+}
+
+impl Primitive<'_> {
+    /// Creates the primitive of a body with `mesh` and `radius`. Meshes of
+    /// 1 or 2 vertices are circles, capsules or segments, larger ones
+    /// polygons, which `radius` doesn't round.
+    pub fn of(mesh: &[Position], radius: f64) -> Primitive<'_> {
+        match *mesh {
+            [center] => Primitive::Circle { center, radius },
+            [a, b] if radius > 0.0 => Primitive::Capsule {
+                ends: [a, b],
+                radius,
+            },
+            [a, b] => Primitive::Segment { ends: [a, b] },
+            _ => Primitive::Polygon(mesh),
+        }
+    }
+
+    /// Vertices the primitive is grown from.
+    pub fn core(&self) -> &[Position] {
+        match self {
+            Primitive::Polygon(mesh) => mesh,
+            Primitive::Circle { center, .. } => std::slice::from_ref(center),
+            Primitive::Capsule { ends, .. } | Primitive::Segment { ends } => ends,
+        }
+    }
+
+    pub fn radius(&self) -> f64 {
+        match self {
+            Primitive::Circle { radius, .. } | Primitive::Capsule { radius, .. } => *radius,
+            Primitive::Polygon(_) | Primitive::Segment { .. } => 0.0,
+        }
+    }
+}
+
+/// Whether `a` and `b` touch or overlap.
+pub fn overlap(a: &Primitive, b: &Primitive) -> bool {
+    match (a, b) {
+        (Primitive::Polygon(mesh1), Primitive::Polygon(mesh2)) => collided(mesh1, mesh2),
+        (
+            Primitive::Circle {
+                center: c1,
+                radius: r1,
+            },
+            Primitive::Circle {
+                center: c2,
+                radius: r2,
+            },
+        ) => (*c2 - *c1).length() <= r1 + r2,
+        _ => !rounded_separated(a.core(), a.radius(), b.core(), b.radius()),
+    }
+}
+
+/// Whether a part of `a` touches or overlaps a part of `b`.
+pub fn bodies_overlap(a: &ConvexBody, b: &ConvexBody) -> bool {
+    if a.parts().is_empty() && b.parts().is_empty() {
+        return overlap(&a.primitive(), &b.primitive());
+    }
+    a.primitives()
+        .cartesian_product(b.primitives())
+        .any(|(p1, p2)| overlap(&p1, &p2))
+}
+
+/// Parts of `a` and of `b` touching the other body, bit `i` standing for
+/// part `i`, for bodies known to touch.
+pub fn touching_parts(a: &ConvexBody, b: &ConvexBody) -> (u32, u32) {
+    if a.parts().is_empty() && b.parts().is_empty() {
+        return (1, 1);
+    }
+    a.primitives()
+        .enumerate()
+        .cartesian_product(b.primitives().enumerate())
+        .filter(|((_, p1), (_, p2))| overlap(p1, p2))
+        .fold((0, 0), |(parts1, parts2), ((i, _), (j, _))| {
+            (parts1 | 1 << i, parts2 | 1 << j)
+        })
+}
+
+/// Checks for an axis separating `core1` grown by `radius1` from `core2`
+/// grown by `radius2`.
+fn rounded_separated(core1: &[Position], radius1: f64, core2: &[Position], radius2: f64) -> bool {
+    let edge_normals = core1
+        .iter()
+        .circular_tuple_windows()
+        .chain(core2.iter().circular_tuple_windows())
+        .map(|(p1, p2)| (*p2 - *p1).perpendicular());
+    let between = core1
+        .iter()
+        .cartesian_product(core2)
+        .map(|(p1, p2)| *p2 - *p1);
+    edge_normals.chain(between).any(|axis| {
+        let axis = axis.normalize();
+        if axis == Position::ZERO {
+            return false;
+        }
+        let (min1, max1) = project(core1, &axis);
+        let (min2, max2) = project(core2, &axis);
+        max1 + radius1 < min2 - radius2 || max2 + radius2 < min1 - radius1
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::collision::*;
+    use crate::pos;
+
+    const SQUARE: [Position; 4] = positions![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+
+    fn circle(x: f64, y: f64, radius: f64) -> Primitive<'static> {
+        Primitive::Circle {
+            center: pos(x, y),
+            radius,
+        }
+    }
+
+    fn capsule(a: Position, b: Position, radius: f64) -> Primitive<'static> {
+        Primitive::Capsule {
+            ends: [a, b],
+            radius,
+        }
+    }
+
+    fn segment(a: Position, b: Position) -> Primitive<'static> {
+        Primitive::Segment { ends: [a, b] }
+    }
+
+    #[test]
+    fn primitives_of_meshes() {
+        assert_eq!(Primitive::of(&[pos(1.0, 2.0)], 0.5), circle(1.0, 2.0, 0.5));
+        let ends = [pos(0.0, 0.0), pos(1.0, 0.0)];
+        assert_eq!(Primitive::of(&ends, 0.5), capsule(ends[0], ends[1], 0.5));
+        assert_eq!(Primitive::of(&ends, 0.0), segment(ends[0], ends[1]));
+        assert_eq!(Primitive::of(&SQUARE, 0.0), Primitive::Polygon(&SQUARE));
+    }
+
+    #[test]
+    fn circles() {
+        assert!(overlap(&circle(0.0, 0.0, 1.0), &circle(1.5, 0.0, 0.5)));
+        assert!(!overlap(&circle(0.0, 0.0, 1.0), &circle(1.6, 0.0, 0.5)));
+
+        let square = Primitive::Polygon(&SQUARE);
+        assert!(overlap(&circle(1.0, 1.0, 0.1), &square));
+        assert!(overlap(&circle(3.0, 1.0, 1.0), &square));
+        // Inside the bounding box of the rounded square, off its corner.
+        assert!(!overlap(&circle(2.8, 2.8, 1.0), &square));
+        assert!(overlap(&square, &circle(2.7, 2.7, 1.0)));
+
+        assert!(overlap(
+            &circle(1.0, 1.2, 0.3),
+            &segment(pos(0.0, 1.0), pos(2.0, 1.0))
+        ));
+        assert!(!overlap(
+            &circle(2.3, 1.2, 0.3),
+            &segment(pos(0.0, 1.0), pos(2.0, 1.0))
+        ));
+        assert!(overlap(
+            &circle(2.3, 1.0, 0.3),
+            &capsule(pos(0.0, 1.0), pos(2.0, 1.0), 0.1)
+        ));
+    }
+
+    #[test]
+    fn capsules_and_segments() {
+        let lying = capsule(pos(0.0, 0.0), pos(4.0, 0.0), 0.5);
+        assert!(overlap(&lying, &capsule(pos(0.0, 0.9), pos(4.0, 0.9), 0.5)));
+        assert!(!overlap(
+            &lying,
+            &capsule(pos(0.0, 1.1), pos(4.0, 1.1), 0.5)
+        ));
+        // End to end, diagonally off.
+        assert!(!overlap(
+            &lying,
+            &capsule(pos(4.8, 0.8), pos(6.0, 2.0), 0.5)
+        ));
+        assert!(overlap(&lying, &capsule(pos(4.6, 0.6), pos(6.0, 2.0), 0.5)));
+
+        let crossing = segment(pos(1.0, -1.0), pos(1.0, 1.0));
+        assert!(overlap(&segment(pos(0.0, 0.0), pos(2.0, 0.0)), &crossing));
+        assert!(!overlap(&segment(pos(2.0, 0.0), pos(3.0, 0.0)), &crossing));
+        assert!(overlap(&lying, &crossing));
+
+        let square = Primitive::Polygon(&SQUARE);
+        assert!(overlap(&segment(pos(-1.0, 1.0), pos(1.0, 3.0)), &square));
+        assert!(!overlap(&segment(pos(-1.0, 2.0), pos(1.0, 4.0)), &square));
+        assert!(overlap(
+            &capsule(pos(-1.0, 2.0), pos(1.0, 4.0), 0.8),
+            &square
+        ));
+        assert!(!overlap(
+            &capsule(pos(3.0, 0.0), pos(3.0, 2.0), 0.9),
+            &square
+        ));
+    }
+}
@@ -0,0 +1,220 @@
+//! Craters: impacts carve the terrain polyline, and only the partitions
+//! under the crater get rebuilt in the engine.
+
+use crate::{add_terrain, partition_terrain_to, pos, terrain_floor, BodyId, Engine, Position};
+use std::ops::Range;
+
+/// Radius of the crater a crashed lander leaves, in meters.
+pub const CRASH_CRATER_RADIUS: f64 = 2.5;
+/// How many segments the bottom of a crater is made of.
+const CRATER_SEGMENTS: usize = 12;
+/// Narrowest segment a crater makes, in meters.
+const MIN_SEGMENT_WIDTH: f64 = 1e-3;
+
+/// Carves a crater of `radius` around `center` into `terrain`, lowering
+/// the ground to the bottom half of the circle. Returns the range of
+/// points of `terrain` the crater replaces, and the points replacing them,
+/// or `None` if the crater doesn't reach the ground.
+pub fn carve(
+    terrain: &[Position],
+    center: &Position,
+    radius: f64,
+) -> Option<(Range<usize>, Vec<Position>)> {
+    let (left, right) = (center.x - radius, center.x + radius);
+    let first = terrain.iter().rposition(|p| p.x <= left).unwrap_or(0);
+    let last = terrain
+        .iter()
+        .position(|p| p.x >= right)
+        .unwrap_or(terrain.len().checked_sub(1)?);
+    if last <= first {
+        return None;
+    }
+    let span = &terrain[first..=last];
+
+    let ground = |x: f64| {
+        let i = span.partition_point(|p| p.x < x).clamp(1, span.len() - 1);
+        let (p1, p2) = (span[i - 1], span[i]);
+        p1.y + (p2.y - p1.y) * (x - p1.x) / (p2.x - p1.x)
+    };
+    let bottom = |x: f64| match radius * radius - (x - center.x).powi(2) {
+        reach if reach < 0.0 => f64::INFINITY,
+        reach => center.y - reach.sqrt(),
+    };
+
+    let mut xs: Vec<f64> = (0..=CRATER_SEGMENTS)
+        .map(|i| left + 2.0 * radius * i as f64 / CRATER_SEGMENTS as f64)
+        .filter(|x| (span[0].x..=span[span.len() - 1].x).contains(x))
+        // Too close to a point of the terrain, it would make a sliver.
+        .filter(|x| span.iter().all(|p| (p.x - x).abs() > MIN_SEGMENT_WIDTH))
+        .chain(span.iter().map(|p| p.x))
+        .collect();
+    xs.sort_by(f64::total_cmp);
+
+    let carved: Vec<bool> = xs.iter().map(|&x| bottom(x) < ground(x) - 1e-9).collect();
+    if !carved.contains(&true) {
+        return None;
+    }
+    // Keeps the points of the crater and of its rim, and every point
+    // the terrain already had.
+    let points = xs
+        .iter()
+        .enumerate()
+        .filter(|&(i, x)| {
+            span.iter().any(|p| p.x == *x)
+                || carved[i.saturating_sub(1)..=(i + 1).min(xs.len() - 1)].contains(&true)
+        })
+        .map(|(_, &x)| pos(x, ground(x).min(bottom(x))))
+        .collect();
+    Some((first..last + 1, points))
+}
+
+impl Engine {
+    /// Carves a crater of `radius` around `center` into `terrain`, and
+    /// replaces the fixed bodies of the partitions it changed. `bodies`
+    /// are those of every segment of `terrain`, as `add_terrain` returns
+    /// them, going down to `floor`. Returns whether the crater reached
+    /// the ground.
+    ///
+    /// A crater getting closer than `TERRAIN_DEPTH` to the floor lowers
+    /// it, and every partition is rebuilt down to the new one.
+    pub fn carve_crater(
+        &mut self,
+        terrain: &mut Vec<Position>,
+        bodies: &mut Vec<(BodyId, bool)>,
+        floor: &mut f64,
+        center: &Position,
+        radius: f64,
+    ) -> bool {
+        let Some((span, points)) = carve(terrain, center, radius) else {
+            return false;
+        };
+        let lowest = terrain_floor(&points);
+        if lowest < *floor {
+            *floor = lowest;
+            terrain.splice(span, points);
+            let added = add_terrain(self, &partition_terrain_to(terrain, *floor));
+            for (id, _) in std::mem::replace(bodies, added) {
+                self.remove_body(id);
+            }
+            return true;
+        }
+        let added = add_terrain(self, &partition_terrain_to(&points, *floor));
+        let removed: Vec<_> = bodies.splice(span.start..span.end - 1, added).collect();
+        for (id, _) in removed {
+            self.remove_body(id);
+        }
+        terrain.splice(span, points);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crater::*;
+    use crate::*;
+
+    #[test]
+    fn carve_flat_ground() {
+        let terrain = positions![(0.0, 0.0), (10.0, 0.0), (20.0, 0.0)];
+        let (span, points) = carve(&terrain, &pos(10.0, 1.0), 2.0).unwrap();
+        assert_eq!(span, 0..3);
+        assert_eq!(points.first(), Some(&pos(0.0, 0.0)));
+        assert_eq!(points.last(), Some(&pos(20.0, 0.0)));
+        assert!(points.windows(2).all(|w| w[0].x < w[1].x));
+        assert!(points.iter().all(|p| p.y <= 0.0 && p.y >= -1.0 - 1e-9));
+        assert!(points
+            .iter()
+            .any(|p| (p.x - 10.0).abs() < 1e-9 && (p.y + 1.0).abs() < 1e-9));
+        // The ground stays put outside of the crater.
+        assert!(points
+            .iter()
+            .filter(|p| (p.x - 10.0).abs() > 3.0_f64.sqrt() + 1e-9)
+            .all(|p| p.y == 0.0));
+    }
+
+    #[test]
+    fn carve_above_ground() {
+        let terrain = positions![(0.0, 0.0), (10.0, 0.0), (20.0, 0.0)];
+        assert!(carve(&terrain, &pos(10.0, 3.0), 2.0).is_none());
+        assert!(carve(&terrain, &pos(30.0, 0.0), 2.0).is_none());
+    }
+
+    #[test]
+    fn carve_crater_rebuilds_only_the_partitions_under_it() {
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let mut terrain = positions![(0.0, 5.0), (5.0, 5.0), (10.0, 8.0), (15.0, 3.0)].to_vec();
+        let mut bodies = add_terrain(&mut engine, &partition_terrain(&terrain));
+        let mut floor = terrain_floor(&terrain);
+        let (first, last) = (bodies[0], bodies[2]);
+
+        assert!(engine.carve_crater(&mut terrain, &mut bodies, &mut floor, &pos(7.0, 6.0), 1.5));
+        assert_eq!(floor, -7.0);
+        assert_eq!(bodies.len(), terrain.len() - 1);
+        assert_eq!(bodies[0], first);
+        // All partitions still go down to the same floor.
+        for (id, _) in &bodies {
+            let mesh = engine.get_body(*id).unwrap().mesh();
+            assert!((mesh[2].y + 7.0).abs() < 1e-9 && (mesh[3].y + 7.0).abs() < 1e-9);
+        }
+        assert_eq!(bodies[bodies.len() - 1], last);
+        // Every segment has a body built out of it.
+        for (segment, (id, _)) in terrain.windows(2).zip(&bodies) {
+            let mesh = engine.get_body(*id).unwrap().mesh();
+            assert!((mesh[0] - segment[0]).length() < 1e-9);
+            assert!((mesh[1] - segment[1]).length() < 1e-9);
+        }
+
+        // A ball falling in the crater hits its bottom, below where the
+        // ground used to be.
+        let ball = engine.add_body(ConvexBody::circle(1.0, pos(7.0, 10.0), 0.2));
+        while touched_body(&engine, ball).is_none() {
+            engine.tick(1.0 / 60.0);
+        }
+        let floor = touched_body(&engine, ball).unwrap();
+        assert!(bodies[1..bodies.len() - 1]
+            .iter()
+            .any(|(id, _)| *id == floor));
+        assert!(engine.get_body(ball).unwrap().position().y < 6.0);
+    }
+
+    #[test]
+    fn craters_over_craters() {
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let mut terrain = positions![(0.0, 5.0), (10.0, 5.0), (20.0, 5.0)].to_vec();
+        let mut bodies = add_terrain(&mut engine, &partition_terrain(&terrain));
+        let mut floor = terrain_floor(&terrain);
+        for x in [9.0, 10.0, 10.5] {
+            assert!(engine.carve_crater(&mut terrain, &mut bodies, &mut floor, &pos(x, 5.5), 2.0));
+        }
+        // The same crater again doesn't dig any deeper.
+        let before = terrain.clone();
+        let crater = pos(10.5, 5.5);
+        assert!(!engine.carve_crater(&mut terrain, &mut bodies, &mut floor, &crater, 2.0));
+        assert_eq!(terrain, before);
+        assert_eq!(bodies.len(), terrain.len() - 1);
+        assert!(terrain.windows(2).all(|w| w[1].x - w[0].x > 1e-3));
+        assert!(terrain.iter().all(|p| p.y <= 5.0 && p.y >= 3.5 - 1e-9));
+    }
+
+    #[test]
+    fn deep_craters_lower_the_whole_floor() {
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let mut terrain = positions![(0.0, 5.0), (10.0, 5.0), (20.0, 5.0)].to_vec();
+        let mut bodies = add_terrain(&mut engine, &partition_terrain(&terrain));
+        let mut floor = terrain_floor(&terrain);
+        let before = bodies.clone();
+
+        assert!(engine.carve_crater(&mut terrain, &mut bodies, &mut floor, &pos(10.0, 5.0), 2.0));
+        assert_eq!(floor, 3.0 - TERRAIN_DEPTH);
+        assert!(before.iter().all(|(id, _)| engine.get_body(*id).is_none()));
+        let partitions = partition_terrain(&terrain);
+        assert_eq!(bodies.len(), partitions.len());
+        for ((id, _), partition) in bodies.iter().zip(&partitions) {
+            let mesh = engine.get_body(*id).unwrap().mesh();
+            assert!(mesh
+                .iter()
+                .zip(&partition.mesh)
+                .all(|(p, q)| (*p - *q).length() < 1e-9));
+        }
+    }
+}
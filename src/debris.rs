@@ -0,0 +1,232 @@
+//! Debris: a crashed body breaks into convex pieces, cut one at a time
+//! through the center of mass of the largest piece along a random line.
+//! Pieces keep the motion of the body they come from, get kicked around a
+//! bit, and rest on the ground on their corners, which are legs pushing
+//! them out of it whichever way they land.
+
+use crate::collision::Primitive;
+use crate::gear::Leg;
+use crate::{layer, shape, v, BodyId, ConvexBody, Engine, Position, Vector};
+use rand::Rng;
+use std::f64::consts::PI;
+
+/// How many pieces a crashed lander breaks into.
+pub const DEBRIS_PIECES: usize = 6;
+
+/// Corners of the pieces, in N/m, N·s/m and N·s/m of sliding per kilogram
+/// of the piece, and as a friction coefficient. Light pieces with stiff
+/// corners would need shorter ticks.
+const CORNER_STIFFNESS: f64 = 200.0;
+const CORNER_DAMPING: f64 = 6.0;
+const CORNER_GRIP: f64 = 8.0;
+const CORNER_FRICTION: f64 = 0.6;
+/// Largest random impulse a piece gets, in N·s.
+const SCATTER_IMPULSE: f64 = 1.5;
+/// Largest random spin a piece gets, in rad/s.
+const SCATTER_SPIN: f64 = 2.0;
+
+/// Cuts the convex `mesh` along the line through `point` going along
+/// `direction`. Returns the part on the left of the line, then the one on
+/// the right, either of which can be empty.
+pub fn split(
+    mesh: &[Position],
+    point: Position,
+    direction: Vector,
+) -> (Vec<Position>, Vec<Position>) {
+    let mut left = vec![];
+    let mut right = vec![];
+    let side = |p: Position| direction.cross(p - point);
+    for (i, p) in mesh.iter().enumerate() {
+        let q = mesh[(i + 1) % mesh.len()];
+        let (sp, sq) = (side(*p), side(q));
+        if sp >= 0.0 {
+            left.push(*p);
+        }
+        if sp <= 0.0 {
+            right.push(*p);
+        }
+        if sp * sq < 0.0 {
+            let crossing = *p + (q - *p) * (sp / (sp - sq));
+            left.push(crossing);
+            right.push(crossing);
+        }
+    }
+    (left, right)
+}
+
+/// Breaks the convex `mesh` into at most `pieces` convex pieces, cutting
+/// the largest one in two until there are enough.
+pub fn fracture<R: Rng>(mesh: &[Position], pieces: usize, rng: &mut R) -> Vec<Vec<Position>> {
+    let mut fragments = vec![mesh.to_vec()];
+    while fragments.len() < pieces {
+        let (largest, _) = fragments
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| shape::area(a).total_cmp(&shape::area(b)))
+            .unwrap();
+        let angle = rng.gen_range(0.0..PI);
+        let direction = v(angle.cos(), angle.sin());
+        let center = shape::center_of_mass(&fragments[largest]);
+        let (left, right) = split(&fragments[largest], center, direction);
+        if shape::validate(&left).is_err() || shape::validate(&right).is_err() {
+            break;
+        }
+        fragments[largest] = left;
+        fragments.push(right);
+    }
+    fragments
+}
+
+/// Body of a piece of `mass` occupying `primitive`, sitting on corners
+/// at the vertices of its core.
+fn piece(mass: f64, primitive: &Primitive) -> ConvexBody {
+    let body = match *primitive {
+        Primitive::Polygon(mesh) => ConvexBody::still_body(mass, mesh),
+        Primitive::Circle { center, radius } => ConvexBody::circle(mass, center, radius),
+        Primitive::Capsule { ends, radius } => ConvexBody::capsule(mass, ends[0], ends[1], radius),
+        Primitive::Segment { ends } => ConvexBody::segment(mass, ends[0], ends[1]),
+    };
+    let corners = body
+        .shape()
+        .iter()
+        .map(|tip| Leg {
+            grip: CORNER_GRIP * mass,
+            ..Leg::corner(
+                *tip,
+                CORNER_STIFFNESS * mass,
+                CORNER_DAMPING * mass,
+                CORNER_FRICTION,
+            )
+        })
+        .collect();
+    body.with_layers(layer::DEBRIS, layer::TERRAIN | layer::DEBRIS)
+        .with_legs(corners)
+}
+
+impl Engine {
+    /// Replaces the body behind `id` by debris: its own polygon breaks into
+    /// `pieces` pieces and each of its parts makes one more. Pieces share
+    /// the mass by their areas, move along with the body and get a random
+    /// kick. Returns the ids of the pieces, or `None` if there was no body.
+    pub fn shatter<R: Rng>(
+        &mut self,
+        id: BodyId,
+        pieces: usize,
+        rng: &mut R,
+    ) -> Option<Vec<BodyId>> {
+        let body = self.remove_body(id)?;
+        let mut fragments: Vec<Vec<Position>> = vec![];
+        let mut primitives: Vec<Primitive> = vec![];
+        for (i, primitive) in body.primitives().enumerate() {
+            match primitive {
+                Primitive::Polygon(mesh) if i == 0 => fragments = fracture(mesh, pieces, rng),
+                _ => primitives.push(primitive),
+            }
+        }
+        let primitives: Vec<Primitive> = fragments
+            .iter()
+            .map(|mesh| Primitive::Polygon(mesh))
+            .chain(primitives)
+            .collect();
+
+        let areas: Vec<f64> = primitives
+            .iter()
+            .map(|p| shape::rounded_area(p.core(), p.radius()))
+            .collect();
+        let total: f64 = areas.iter().sum();
+        let center = body.position();
+        let debris = primitives
+            .iter()
+            .zip(areas)
+            .filter(|(_, area)| total == 0.0 || *area > 0.0)
+            .map(|(primitive, area)| {
+                let share = if total == 0.0 {
+                    1.0 / primitives.len() as f64
+                } else {
+                    area / total
+                };
+                let mut piece = piece(body.mass * share, primitive);
+                let r = piece.position() - center;
+                let angle = rng.gen_range(0.0..2.0 * PI);
+                let kick =
+                    v(angle.cos(), angle.sin()) * rng.gen_range(0.0..SCATTER_IMPULSE) / piece.mass;
+                piece.velocity = body.velocity + r.perpendicular() * body.angular_velocity + kick;
+                piece.angular_velocity =
+                    body.angular_velocity + rng.gen_range(-SCATTER_SPIN..SCATTER_SPIN);
+                self.add_body(piece)
+            })
+            .collect();
+        Some(debris)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::debris::*;
+    use crate::{create_lander, pos, sleep, MOON_GRAVITY};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SQUARE: [Position; 4] = positions![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+
+    #[test]
+    fn split_square() {
+        let (left, right) = split(&SQUARE, pos(1.0, 1.0), v(0.0, 1.0));
+        assert_eq!(shape::area(&left), 2.0);
+        assert_eq!(shape::area(&right), 2.0);
+        assert!(left.iter().all(|p| p.x <= 1.0));
+        assert!(right.iter().all(|p| p.x >= 1.0));
+
+        let (left, right) = split(&SQUARE, pos(3.0, 0.0), v(0.0, 1.0));
+        assert_eq!(left, SQUARE.to_vec());
+        assert!(right.is_empty());
+    }
+
+    #[test]
+    fn fracture_keeps_the_area() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let fragments = fracture(&SQUARE, 6, &mut rng);
+        assert_eq!(fragments.len(), 6);
+        assert!(fragments.iter().all(|f| shape::validate(f).is_ok()));
+        let area: f64 = fragments.iter().map(|f| shape::area(f)).sum();
+        assert!((area - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn debris_comes_to_rest_on_the_ground() {
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let mut lander = create_lander();
+        lander.velocity = v(2.0, -4.0);
+        lander.angular_velocity = 0.5;
+        let lander = engine.add_body(lander);
+        engine.add_body(ConvexBody::fixed_body(&positions![
+            (-100.0, 97.9),
+            (200.0, 97.9),
+            (200.0, 80.0),
+            (-100.0, 80.0)
+        ]));
+        let mut rng = StdRng::seed_from_u64(3);
+        let debris = engine.shatter(lander, DEBRIS_PIECES, &mut rng).unwrap();
+        assert!(engine.get_body(lander).is_none());
+        assert!(engine.shatter(lander, DEBRIS_PIECES, &mut rng).is_none());
+        assert_eq!(debris.len(), DEBRIS_PIECES);
+        let mass: f64 = debris
+            .iter()
+            .map(|id| engine.get_body(*id).unwrap().mass)
+            .sum();
+        assert!((mass - 10.0).abs() < 1e-9);
+
+        for _ in 0..60 * 20 {
+            engine.tick(1.0 / 60.0);
+        }
+        debris.iter().for_each(|id| {
+            let piece = engine.get_body(*id).unwrap();
+            assert!(
+                piece.mesh().iter().all(|p| p.y > 97.8),
+                "{:?}",
+                piece.mesh()
+            );
+            assert!(piece.sleeping || sleep::specific_kinetic_energy(piece) < 1e-2);
+        });
+    }
+}
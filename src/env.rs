@@ -0,0 +1,509 @@
+//! Gym-style environment around the lander, to train agents directly
+//! against the simulator: `reset` starts an episode on a seeded terrain
+//! and `step` advances it by one action.
+
+use crate::atmosphere::Atmosphere;
+use crate::autopilot::{find_pad, Pad};
+use crate::gear::{follow_touchdown, Touchdown};
+use crate::gravity::{Gravity, Planet};
+use crate::{
+    add_terrain, apply_thrust, bounds, create_lander, generate_terrain_with, partition_terrain,
+    pos, v, BodyId, ConvexBody, Engine, Landing, Position, Thrust,
+};
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::f64::consts::PI;
+
+/// Episodes end when the lander leaves the screen.
+const LEFT_LIMIT: f64 = 0.0;
+const RIGHT_LIMIT: f64 = 100.0;
+const TOP_LIMIT: f64 = 200.0;
+
+/// A group of values in the observation vector.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feature {
+    /// Center of the lander, in meters.
+    Position,
+    /// Velocity of the lander, in m/s.
+    Velocity,
+    /// Attitude angle, counterclockwise from upright in radians, and
+    /// angular velocity, in rad/s.
+    Attitude,
+    /// Center of the lander relative to the center of the landing pad.
+    PadOffset,
+    /// Distance to the terrain along `count` rays fanning out below the
+    /// lander, from pointing left to pointing right, capped at `length`.
+    TerrainRays { count: usize, length: f64 },
+}
+
+impl Feature {
+    fn len(&self) -> usize {
+        match self {
+            Feature::TerrainRays { count, .. } => *count,
+            _ => 2,
+        }
+    }
+}
+
+/// Weights of the reward terms. Distance and speed are shaped as a
+/// potential, so an agent is rewarded for getting closer and slower
+/// rather than for staying close.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rewards {
+    /// Per meter of distance to the pad.
+    pub distance: f64,
+    /// Per m/s of speed.
+    pub speed: f64,
+    /// Per second of main engine at full thrust.
+    pub fuel: f64,
+    pub landed: f64,
+    pub crashed: f64,
+}
+
+impl Default for Rewards {
+    fn default() -> Rewards {
+        Rewards {
+            distance: 1.0,
+            speed: 1.0,
+            fuel: 0.3,
+            landed: 100.0,
+            crashed: -100.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvConfig {
+    /// Simulated seconds per step.
+    pub dt: f64,
+    pub gravity: Gravity,
+    pub atmosphere: Option<Atmosphere>,
+    /// Episodes are cut after this many simulated seconds.
+    pub time_limit: f64,
+    pub observation: Vec<Feature>,
+    pub rewards: Rewards,
+}
+
+impl Default for EnvConfig {
+    fn default() -> EnvConfig {
+        EnvConfig {
+            dt: 1.0 / 20.0,
+            gravity: Planet::Moon.gravity(),
+            atmosphere: None,
+            time_limit: 120.0,
+            observation: vec![Feature::PadOffset, Feature::Velocity],
+            rewards: Rewards::default(),
+        }
+    }
+}
+
+impl EnvConfig {
+    /// Default configuration with the gravity and atmosphere of `planet`.
+    pub fn for_planet(planet: Planet) -> EnvConfig {
+        EnvConfig {
+            gravity: planet.gravity(),
+            atmosphere: planet.atmosphere(),
+            ..EnvConfig::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Landed,
+    Crashed,
+    OutOfBounds,
+    TimeOut,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepInfo {
+    /// How the episode ended, once it has.
+    pub outcome: Option<Outcome>,
+    /// Simulated seconds since the episode started.
+    pub time: f64,
+    /// Seconds of main engine at full thrust since the episode started.
+    pub fuel_used: f64,
+}
+
+pub struct LanderEnv {
+    config: EnvConfig,
+    engine: Engine,
+    lander: BodyId,
+    terrain: Vec<Position>,
+    terrain_safety: Vec<(BodyId, bool)>,
+    pad: Pad,
+    /// Landing on the legs, from the first contact on.
+    touchdown: Option<Touchdown>,
+    info: StepInfo,
+    /// Value of the shaping potential at the last step.
+    potential: f64,
+}
+
+/// Distance from `origin`, along direction `angle`, to the closest segment
+/// of `terrain`, if it's closer than `length`.
+fn cast_ray(origin: &Position, angle: f64, length: f64, terrain: &[Position]) -> f64 {
+    let direction = v(angle.cos(), angle.sin());
+    terrain
+        .iter()
+        .tuple_windows()
+        .filter_map(|(p1, p2)| {
+            let edge = *p2 - *p1;
+            let denominator = direction.cross(edge);
+            if denominator.abs() < f64::EPSILON {
+                return None;
+            }
+            let w = *p1 - *origin;
+            let t = w.cross(edge) / denominator;
+            let u = w.cross(direction) / denominator;
+            (t >= 0.0 && (0.0..=1.0).contains(&u)).then_some(t)
+        })
+        .fold(length, f64::min)
+}
+
+impl LanderEnv {
+    /// Creates an environment. Call `reset` to start the first episode.
+    pub fn create(config: EnvConfig) -> LanderEnv {
+        LanderEnv::create_with_seed(config, 0)
+    }
+
+    /// Creates an environment with its first episode started on the
+    /// terrain generated from `seed`, as `reset` would.
+    pub fn create_with_seed(config: EnvConfig, seed: u64) -> LanderEnv {
+        let mut engine = Engine::create_with(config.gravity.clone());
+        let lander = engine.add_body(create_lander());
+        let mut env = LanderEnv {
+            engine,
+            config,
+            lander,
+            terrain: vec![],
+            terrain_safety: vec![],
+            pad: Pad {
+                left: 0.0,
+                right: 0.0,
+                height: 0.0,
+            },
+            touchdown: None,
+            info: StepInfo {
+                outcome: None,
+                time: 0.0,
+                fuel_used: 0.0,
+            },
+            potential: 0.0,
+        };
+        env.reset(seed);
+        env
+    }
+
+    /// Length of the observation vectors.
+    pub fn observation_len(&self) -> usize {
+        self.config.observation.iter().map(Feature::len).sum()
+    }
+
+    pub fn lander(&self) -> &ConvexBody {
+        self.engine.get_body(self.lander).unwrap()
+    }
+
+    /// Strength of gravity where the lander is.
+    pub fn gravity(&self) -> f64 {
+        self.engine.gravity.strength_at(&self.lander().position())
+    }
+
+    pub fn terrain(&self) -> &[Position] {
+        &self.terrain
+    }
+
+    pub fn pad(&self) -> &Pad {
+        &self.pad
+    }
+
+    /// Details about the episode so far.
+    pub fn info(&self) -> &StepInfo {
+        &self.info
+    }
+
+    /// Landing on the legs, once the lander touched something.
+    pub fn touchdown(&self) -> Option<&Touchdown> {
+        self.touchdown.as_ref()
+    }
+
+    /// Starts a new episode on the terrain generated from `seed`.
+    pub fn reset(&mut self, seed: u64) -> Vec<f64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        self.terrain = generate_terrain_with(&mut rng);
+        let partitions = partition_terrain(&self.terrain);
+        self.engine = Engine::create_with(self.config.gravity.clone());
+        self.engine.atmosphere = self.config.atmosphere.clone();
+        self.lander = self.engine.add_body(create_lander());
+        self.terrain_safety = add_terrain(&mut self.engine, &partitions);
+        // Generated terrain always has a landing site.
+        self.pad = find_pad(&partitions, 50.0).unwrap();
+        self.touchdown = None;
+        self.info = StepInfo {
+            outcome: None,
+            time: 0.0,
+            fuel_used: 0.0,
+        };
+        self.potential = self.shaping_potential();
+        self.observe()
+    }
+
+    /// Applies `action` for one step and returns the observation, the
+    /// reward, whether the episode is over and details about it. Stepping
+    /// a finished episode does nothing.
+    pub fn step(&mut self, action: &Thrust) -> (Vec<f64>, f64, bool, StepInfo) {
+        let reward = self.advance(action);
+        (
+            self.observe(),
+            reward,
+            self.info.outcome.is_some(),
+            self.info.clone(),
+        )
+    }
+
+    /// Same as `step`, without observing the lander, and returns only the
+    /// reward. The rest is in `info` and `observe`.
+    pub fn advance(&mut self, action: &Thrust) -> f64 {
+        if self.info.outcome.is_some() {
+            return 0.0;
+        }
+
+        let dt = self.config.dt;
+        let main = action.main.clamp(0.0, 1.0);
+        apply_thrust(self.engine.get_body_mut(self.lander).unwrap(), action);
+        self.engine.tick(dt);
+        self.info.time += dt;
+        self.info.fuel_used += main * dt;
+
+        let rewards = &self.config.rewards;
+        let potential = self.shaping_potential();
+        let mut reward = potential - self.potential - rewards.fuel * main * dt;
+        self.potential = potential;
+
+        let landing = follow_touchdown(
+            &mut self.touchdown,
+            &self.engine,
+            self.lander,
+            &self.terrain_safety,
+            dt,
+        );
+        self.info.outcome = self.outcome(landing);
+        reward += match self.info.outcome {
+            Some(Outcome::Landed) => rewards.landed,
+            Some(Outcome::Crashed) | Some(Outcome::OutOfBounds) => rewards.crashed,
+            _ => 0.0,
+        };
+        reward
+    }
+
+    fn center(&self) -> Position {
+        let (min, max) = bounds(self.lander().mesh());
+        (min + max) / 2.0
+    }
+
+    fn shaping_potential(&self) -> f64 {
+        let center = self.center();
+        let velocity = &self.lander().velocity;
+        let distance = (center - pos(self.pad.center(), self.pad.height)).length();
+        let speed = velocity.length();
+        -(self.config.rewards.distance * distance + self.config.rewards.speed * speed)
+    }
+
+    /// How the episode ended, given how the `landing` went if it's decided.
+    fn outcome(&self, landing: Option<Landing>) -> Option<Outcome> {
+        match landing {
+            Some(Landing::Safe) => return Some(Outcome::Landed),
+            Some(Landing::Crash) => return Some(Outcome::Crashed),
+            None => {}
+        }
+        let center = self.center();
+        if !(LEFT_LIMIT..=RIGHT_LIMIT).contains(&center.x) || center.y > TOP_LIMIT {
+            return Some(Outcome::OutOfBounds);
+        }
+        if self.info.time >= self.config.time_limit {
+            return Some(Outcome::TimeOut);
+        }
+        None
+    }
+
+    /// Observation vector of the lander as it is now.
+    pub fn observe(&self) -> Vec<f64> {
+        let mut observation = Vec::with_capacity(self.observation_len());
+        self.observe_into(&mut observation);
+        observation
+    }
+
+    /// Appends the observation vector to `observation`.
+    pub fn observe_into(&self, observation: &mut Vec<f64>) {
+        let center = self.center();
+        let velocity = &self.lander().velocity;
+        self.config
+            .observation
+            .iter()
+            .for_each(|feature| match feature {
+                Feature::Position => observation.extend([center.x, center.y]),
+                Feature::Velocity => observation.extend([velocity.x, velocity.y]),
+                Feature::Attitude => {
+                    let lander = self.lander();
+                    let angle = (lander.angle() + PI).rem_euclid(2.0 * PI) - PI;
+                    observation.extend([angle, lander.angular_velocity])
+                }
+                Feature::PadOffset => {
+                    observation.extend([center.x - self.pad.center(), center.y - self.pad.height])
+                }
+                Feature::TerrainRays { count, length } => {
+                    observation.extend((0..*count).map(|i| {
+                        let angle = if *count == 1 {
+                            -PI / 2.0
+                        } else {
+                            -PI + PI * i as f64 / (*count - 1) as f64
+                        };
+                        cast_ray(&center, angle, *length, &self.terrain)
+                    }))
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::autopilot::Autopilot;
+    use crate::env::*;
+    use crate::MOON_GRAVITY;
+
+    fn run(env: &mut LanderEnv, mut policy: impl FnMut(&LanderEnv) -> Thrust) -> (f64, StepInfo) {
+        let mut total = 0.0;
+        loop {
+            let action = policy(env);
+            let (_, reward, done, info) = env.step(&action);
+            total += reward;
+            if done {
+                return (total, info);
+            }
+        }
+    }
+
+    #[test]
+    fn reset_is_deterministic() {
+        let mut env = LanderEnv::create(EnvConfig::default());
+        let first = env.reset(7);
+        let terrain = env.terrain().to_vec();
+        env.step(&Thrust {
+            main: 1.0,
+            lateral: 0.5,
+        });
+        assert_eq!(env.reset(7), first);
+        assert_eq!(env.terrain(), terrain.as_slice());
+        env.reset(8);
+        assert_ne!(env.terrain(), terrain.as_slice());
+    }
+
+    #[test]
+    fn observation_follows_config() {
+        let config = EnvConfig {
+            observation: vec![
+                Feature::Position,
+                Feature::Attitude,
+                Feature::TerrainRays {
+                    count: 5,
+                    length: 500.0,
+                },
+            ],
+            ..EnvConfig::default()
+        };
+        let mut env = LanderEnv::create(config);
+        let observation = env.reset(1);
+        assert_eq!(env.observation_len(), 9);
+        assert_eq!(observation.len(), 9);
+        assert_eq!(&observation[..4], &[50.0, 99.0, 0.0, 0.0]);
+        // Rays pointing sideways run parallel to the ground and miss it.
+        assert_eq!(observation[4], 500.0);
+        assert!(observation[6] < 99.0 && observation[6] > 99.0 - 20.0);
+    }
+
+    #[test]
+    fn attitude_is_observed() {
+        let config = EnvConfig {
+            observation: vec![Feature::Attitude],
+            ..EnvConfig::default()
+        };
+        let mut env = LanderEnv::create(config);
+        let lander = env.engine.get_body_mut(env.lander).unwrap();
+        lander.rotate(2.0 * PI + 0.3);
+        lander.angular_velocity = -0.2;
+        let observation = env.observe();
+        assert!((observation[0] - 0.3).abs() < 1e-9);
+        assert_eq!(observation[1], -0.2);
+    }
+
+    #[test]
+    fn ray_casting() {
+        let terrain = positions![(0.0, 0.0), (10.0, 0.0), (20.0, 10.0)];
+        let origin = pos(5.0, 5.0);
+        assert!((cast_ray(&origin, -PI / 2.0, 100.0, &terrain) - 5.0).abs() < 1e-9);
+        assert_eq!(cast_ray(&origin, PI / 2.0, 100.0, &terrain), 100.0);
+        assert_eq!(cast_ray(&origin, -PI / 2.0, 2.0, &terrain), 2.0);
+        let diagonal = cast_ray(&pos(15.0, 10.0), -PI / 2.0, 100.0, &terrain);
+        assert!((diagonal - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn free_fall_crashes() {
+        let mut env = LanderEnv::create(EnvConfig::default());
+        env.reset(3);
+        let (reward, info) = run(&mut env, |_| Thrust::default());
+        assert_eq!(info.outcome, Some(Outcome::Crashed));
+        assert_eq!(env.touchdown().unwrap().landing(), Some(Landing::Crash));
+        assert_eq!(info.fuel_used, 0.0);
+        assert!(reward < 0.0);
+    }
+
+    #[test]
+    fn hovering_times_out() {
+        let config = EnvConfig {
+            time_limit: 5.0,
+            ..EnvConfig::default()
+        };
+        let mut env = LanderEnv::create(config);
+        env.reset(3);
+        let hover = MOON_GRAVITY * env.lander().mass / crate::MAIN_ENGINE_THRUST;
+        let (_, info) = run(&mut env, |_| Thrust {
+            main: hover,
+            lateral: 0.0,
+        });
+        assert_eq!(info.outcome, Some(Outcome::TimeOut));
+        assert!(info.time >= 5.0);
+    }
+
+    #[test]
+    fn autopilot_lands_and_is_rewarded() {
+        let mut env = LanderEnv::create(EnvConfig::default());
+        for seed in 0..5 {
+            env.reset(seed);
+            let partitions = partition_terrain(env.terrain());
+            let mut autopilot = Autopilot::create(&partitions, 50.0).unwrap();
+            let dt = env.config.dt;
+            let (reward, info) = run(&mut env, |env| {
+                autopilot.command(env.lander(), env.gravity(), dt)
+            });
+            assert_eq!(info.outcome, Some(Outcome::Landed));
+            // The episode goes on until the lander is at rest on its legs.
+            let touchdown = env.touchdown().unwrap();
+            assert!(touchdown.safe && touchdown.stroke > 0.0);
+            assert!(reward > 100.0);
+        }
+    }
+
+    #[test]
+    fn planets_change_the_fall() {
+        let fall_time = |planet| {
+            let mut env = LanderEnv::create(EnvConfig::for_planet(planet));
+            env.reset(4);
+            run(&mut env, |_| Thrust::default()).1.time
+        };
+        let moon = fall_time(Planet::Moon);
+        assert!(fall_time(Planet::Mars) < moon);
+        assert!(fall_time(Planet::Titan) > moon);
+    }
+}
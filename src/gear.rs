@@ -0,0 +1,423 @@
+//! Landing legs: spring-damper elements at the tips of a body's legs. A tip
+//! sinking into a fixed body compresses its leg, which pushes back along the
+//! ground normal and grips the ground against sliding. Legs are off the
+//! body's origin, so they turn the body, and a lander coming down on one leg
+//! first can tip over.
+
+use crate::orbit::to_local;
+use crate::{shape, touched_body, v, BodyId, ConvexBody, Engine, Landing, Part, Position, Vector};
+use itertools::Itertools;
+use std::f64::consts::PI;
+
+/// Legs of the lander, in N/m, N·s/m and as a friction coefficient.
+pub const LEG_STIFFNESS: f64 = 50.0;
+pub const LEG_DAMPING: f64 = 15.0;
+pub const LEG_FRICTION: f64 = 0.6;
+/// Compression at which a leg of the lander bottoms out, in meters.
+pub const MAX_STROKE: f64 = 0.28;
+/// Largest force a leg of the lander takes without breaking, in newtons.
+pub const MAX_LEG_LOAD: f64 = 30.0;
+/// Largest angle off the vertical the lander can stay up at, in radians.
+pub const MAX_TILT: f64 = 0.35;
+
+/// How hard the tips of the lander grip the ground, in N·s/m of sliding.
+const GRIP: f64 = 100.0;
+/// Speed under which a lander counts as still, in m/s and rad/s.
+const REST_SPEED: f64 = 0.05;
+/// How long a lander has to stay still to be at rest, in seconds.
+const REST_TIME: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leg {
+    /// Tip of the leg, from the origin of the body, as if the body had
+    /// never turned. The leg compresses along the vertical of the body.
+    pub tip: Vector,
+    /// In N/m of compression.
+    pub stiffness: f64,
+    /// In N·s/m of compression speed.
+    pub damping: f64,
+    /// Friction coefficient of the tip on the ground.
+    pub friction: f64,
+    /// How hard the tip grips the ground, in N·s/m of sliding, up to
+    /// friction.
+    pub grip: f64,
+    /// Compression in the last tick, in meters.
+    pub stroke: f64,
+    /// Force on the leg in the last tick, in newtons.
+    pub load: f64,
+    /// Corners compress against gravity rather than along the vertical
+    /// of the body, as they hold it up whichever way it's turned.
+    pub corner: bool,
+}
+
+impl Leg {
+    pub fn create(tip: Vector, stiffness: f64, damping: f64, friction: f64) -> Leg {
+        Leg {
+            tip,
+            stiffness,
+            damping,
+            friction,
+            grip: GRIP,
+            stroke: 0.0,
+            load: 0.0,
+            corner: false,
+        }
+    }
+
+    /// Creates a corner at `tip`, which holds up a body lying on it
+    /// whichever way the body is turned.
+    pub fn corner(tip: Vector, stiffness: f64, damping: f64, friction: f64) -> Leg {
+        Leg {
+            corner: true,
+            ..Leg::create(tip, stiffness, damping, friction)
+        }
+    }
+}
+
+/// Legs of the lander, at the bottom corners of its mesh.
+pub fn lander_legs() -> Vec<Leg> {
+    [v(-1.0, -1.0), v(1.0, -1.0)]
+        .into_iter()
+        .map(|tip| Leg::create(tip, LEG_STIFFNESS, LEG_DAMPING, LEG_FRICTION))
+        .collect()
+}
+
+/// Moment of inertia of `body` around its origin, taking it as uniform.
+pub fn moment_of_inertia(body: &ConvexBody) -> f64 {
+    body.mass * body.unit_inertia
+}
+
+/// Moment of inertia per kilogram, around the origin, of a uniform body
+/// of `core` grown by `radius`, and made of `parts` as well.
+pub(crate) fn unit_inertia(core: &[Position], radius: f64, parts: &[Part]) -> f64 {
+    if parts.is_empty() {
+        return shape::rounded_unit_inertia(core, radius);
+    }
+    // Parts share the mass by their areas.
+    let parts = parts.iter().map(|part| {
+        let core: Vec<Position> = part
+            .shape()
+            .iter()
+            .map(|p| part.offset().apply(*p))
+            .collect();
+        (core, part.radius())
+    });
+    let (area, inertia) = std::iter::once((core.to_vec(), radius)).chain(parts).fold(
+        (0.0, 0.0),
+        |(area, inertia), (core, radius)| {
+            let part_area = shape::rounded_area(&core, radius);
+            (
+                area + part_area,
+                inertia + part_area * shape::rounded_unit_inertia(&core, radius),
+            )
+        },
+    );
+    if area == 0.0 {
+        0.0
+    } else {
+        inertia / area
+    }
+}
+
+/// How far `tip` has to go along the unit vector `up` to get out of
+/// `mesh`, or straight through the closest edge without `up`, with the
+/// outward normal of the edge it gets out through. Returns `None` if
+/// `tip` isn't inside.
+fn penetration(tip: &Position, up: Option<&Vector>, mesh: &[Position]) -> Option<(f64, Vector)> {
+    if mesh.len() < 3 {
+        return None;
+    }
+    // Normals point out whichever way the mesh goes around.
+    let orientation = mesh
+        .iter()
+        .circular_tuple_windows()
+        .map(|(p1, p2)| p1.cross(*p2))
+        .sum::<f64>()
+        .signum();
+    let mut exit: Option<(f64, Vector)> = None;
+    for (p1, p2) in mesh.iter().circular_tuple_windows() {
+        let n = (*p2 - *p1).perpendicular().normalize() * -orientation;
+        if n == Vector::ZERO {
+            continue;
+        }
+        let distance = (*tip - *p1).dot(n);
+        if distance > 0.0 {
+            return None;
+        }
+        let along = up.map_or(1.0, |up| up.dot(n));
+        if along > 0.0 && exit.as_ref().is_none_or(|(t, _)| -distance / along < *t) {
+            exit = Some((-distance / along, n));
+        }
+    }
+    exit
+}
+
+/// Force and torque the `legs` of `body` get from the fixed bodies of
+/// `ground` their tips are in, under `gravity`. Records the stroke and
+/// load of each leg.
+fn push_legs<'a>(
+    body: &ConvexBody,
+    legs: &mut [Leg],
+    ground: impl Iterator<Item = &'a ConvexBody> + Clone,
+    gravity: Vector,
+) -> (Vector, f64) {
+    let place = body.transform();
+    let up = place.apply_vector(v(0.0, 1.0));
+    // Without gravity, corners get out the shortest way.
+    let rise = -gravity.normalize();
+    let rise = (rise != Vector::ZERO).then_some(&rise);
+    legs.iter_mut()
+        .fold((Vector::ZERO, 0.0), |(force, torque), leg| {
+            let r = place.apply_vector(leg.tip);
+            let tip = place.apply(leg.tip);
+            let contact = ground
+                .clone()
+                .filter(|other| other.fixed && !other.sensor && body.interacts_with(other))
+                .filter_map(|other| {
+                    let direction = if leg.corner { rise } else { Some(&up) };
+                    penetration(&tip, direction, other.mesh())
+                })
+                .max_by(|a, b| a.0.total_cmp(&b.0));
+            let Some((stroke, n)) = contact else {
+                leg.stroke = 0.0;
+                leg.load = 0.0;
+                return (force, torque);
+            };
+            // Corners sink as deep as they are under the edge they get
+            // out through, however steep it is.
+            let stroke = match rise.filter(|_| leg.corner) {
+                Some(rise) => stroke * rise.dot(n),
+                None => stroke,
+            };
+            let tip_velocity = body.velocity + r.perpendicular() * body.angular_velocity;
+            let sinking = -tip_velocity.dot(n);
+            let load = (leg.stiffness * stroke + leg.damping * sinking).max(0.0);
+            let tangent = n.perpendicular();
+            let sliding = tip_velocity.dot(tangent);
+            let grip = (-leg.grip * sliding).clamp(-leg.friction * load, leg.friction * load);
+            let push = n * load + tangent * grip;
+            leg.stroke = stroke;
+            leg.load = load;
+            (force + push, torque + r.cross(push))
+        })
+}
+
+impl Engine {
+    /// Adds the push of legs sunk into fixed bodies to `accelerations` and
+    /// `angular_accelerations`.
+    pub(crate) fn accumulate_leg_forces(&mut self) {
+        let mut accelerations = std::mem::take(&mut self.accelerations);
+        let mut angular_accelerations = std::mem::take(&mut self.angular_accelerations);
+        for index in 0..self.slots.len() {
+            let Some(body) = self.slots[index]
+                .body
+                .as_mut()
+                .filter(|body| body.moving() && !body.legs.is_empty())
+            else {
+                continue;
+            };
+            // The legs are taken out while the body is compared to others.
+            let mut legs = std::mem::take(&mut body.legs);
+            if let Some(body) = self.slots[index].body.as_ref() {
+                let ground = self.slots.iter().filter_map(|slot| slot.body.as_ref());
+                let gravity = self.gravity.at(&body.position());
+                let (force, torque) = push_legs(body, &mut legs, ground, gravity);
+                if force != Vector::ZERO || torque != 0.0 {
+                    accelerations.resize(self.slots.len(), Vector::ZERO);
+                    angular_accelerations.resize(self.slots.len(), 0.0);
+                    accelerations[index] += force / body.mass;
+                    let inertia = moment_of_inertia(body);
+                    if inertia > 0.0 {
+                        angular_accelerations[index] += torque / inertia;
+                    }
+                }
+            }
+            if let Some(body) = self.slots[index].body.as_mut() {
+                body.legs = legs;
+            }
+        }
+        self.accelerations = accelerations;
+        self.angular_accelerations = angular_accelerations;
+    }
+}
+
+/// What a landing went through, from the first contact with the ground
+/// until the lander came to rest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Touchdown {
+    /// Whether the ground touched first is safe for landing.
+    pub safe: bool,
+    /// Velocity at the first contact, relative to the local vertical.
+    pub velocity: Vector,
+    /// Deepest compression of any leg so far, in meters.
+    pub stroke: f64,
+    /// Largest force on any leg so far, in newtons.
+    pub peak_load: f64,
+    /// Angle off the local vertical, counterclockwise, in radians.
+    pub tilt: f64,
+    /// How long the lander has been still, in seconds.
+    still: f64,
+}
+
+impl Touchdown {
+    /// Starts following a touchdown on ground that is, or is not, `safe`,
+    /// at `velocity`.
+    pub fn begin(safe: bool, velocity: &Vector) -> Touchdown {
+        Touchdown {
+            safe,
+            velocity: *velocity,
+            stroke: 0.0,
+            peak_load: 0.0,
+            tilt: 0.0,
+            still: 0.0,
+        }
+    }
+
+    /// Records the legs and the attitude of `lander`, holding `up` as its
+    /// vertical, `dt` seconds after the last record.
+    pub fn record(&mut self, lander: &ConvexBody, up: &Vector, dt: f64) {
+        lander.legs.iter().for_each(|leg| {
+            self.stroke = self.stroke.max(leg.stroke);
+            self.peak_load = self.peak_load.max(leg.load);
+        });
+        let upright = (-up.x).atan2(up.y);
+        self.tilt = (lander.angle() - upright + PI).rem_euclid(2.0 * PI) - PI;
+        let still =
+            lander.velocity.length() < REST_SPEED && lander.angular_velocity.abs() < REST_SPEED;
+        self.still = if still { self.still + dt } else { 0.0 };
+    }
+
+    /// Crash as soon as it's certain, safe once the lander has been at
+    /// rest for a while, and `None` while it can still go either way.
+    pub fn landing(&self) -> Option<Landing> {
+        if !self.safe
+            || self.stroke > MAX_STROKE
+            || self.peak_load > MAX_LEG_LOAD
+            || self.tilt.abs() > MAX_TILT
+        {
+            Some(Landing::Crash)
+        } else if self.still >= REST_TIME {
+            Some(Landing::Safe)
+        } else {
+            None
+        }
+    }
+}
+
+/// Follows the landing of `lander` in `engine` after a tick of `dt`
+/// seconds: starts `touchdown` at its first contact with another body,
+/// safe if `terrain` tags it so, and records it from then on. The lander
+/// holds as its vertical the one gravity gives it. Returns the landing
+/// once it's decided.
+pub fn follow_touchdown(
+    touchdown: &mut Option<Touchdown>,
+    engine: &Engine,
+    lander: BodyId,
+    terrain: &[(BodyId, bool)],
+    dt: f64,
+) -> Option<Landing> {
+    let body = engine.get_body(lander)?;
+    let up = match -engine.gravity.at(&body.position()).normalize() {
+        Vector::ZERO => v(0.0, 1.0),
+        up => up,
+    };
+    if touchdown.is_none() {
+        let touched = touched_body(engine, lander)?;
+        let safe = terrain.iter().any(|&(id, safe)| id == touched && safe);
+        *touchdown = Some(Touchdown::begin(safe, &to_local(&body.velocity, &up)));
+    }
+    let touchdown = touchdown.as_mut()?;
+    touchdown.record(body, &up, dt);
+    touchdown.landing()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gear::*;
+    use crate::{create_lander, pos, Part, Transform2, MOON_GRAVITY};
+
+    /// Drops the lander at `velocity` just above ground turned by `slope`
+    /// radians, and follows it until the landing is decided.
+    fn touch_down(velocity: Vector, slope: f64) -> (Touchdown, Engine, BodyId) {
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let mut lander = create_lander();
+        lander.velocity = velocity;
+        let lander = engine.add_body(lander);
+        let slope = Transform2::rotation_about(pos(51.0, 97.99), slope);
+        let ground = [
+            pos(0.0, 97.99),
+            pos(100.0, 97.99),
+            pos(100.0, 87.99),
+            pos(0.0, 87.99),
+        ]
+        .map(|p| slope.apply(p));
+        engine.add_body(ConvexBody::fixed_body(&ground));
+        let dt = 1.0 / 60.0;
+        let mut touchdown: Option<Touchdown> = None;
+        for _ in 0..60 * 30 {
+            engine.tick(dt);
+            let body = engine.get_body(lander).unwrap();
+            if touchdown.is_none() && engine.has_collisions() {
+                touchdown = Some(Touchdown::begin(true, &body.velocity));
+            }
+            if let Some(touchdown) = &mut touchdown {
+                touchdown.record(body, &v(0.0, 1.0), dt);
+                if touchdown.landing().is_some() {
+                    break;
+                }
+            }
+        }
+        (touchdown.unwrap(), engine, lander)
+    }
+
+    #[test]
+    fn box_moment_of_inertia() {
+        let lander = create_lander();
+        assert!((moment_of_inertia(&lander) - 10.0 * 8.0 / 12.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lander_comes_to_rest_on_its_legs() {
+        let (touchdown, engine, lander) = touch_down(v(0.0, -0.5), 0.0);
+        assert_eq!(touchdown.landing(), Some(Landing::Safe));
+        assert!(touchdown.stroke > 0.1 && touchdown.stroke < MAX_STROKE);
+        assert!(touchdown.peak_load > 0.0 && touchdown.peak_load < MAX_LEG_LOAD);
+
+        // Each leg carries half the weight.
+        let body = engine.get_body(lander).unwrap();
+        let weight = body.mass * MOON_GRAVITY;
+        body.legs.iter().for_each(|leg| {
+            assert!((leg.load - weight / 2.0).abs() < 0.5);
+            assert!((leg.stroke - weight / 2.0 / LEG_STIFFNESS).abs() < 0.01);
+        });
+        assert!(touchdown.tilt.abs() < 1e-6);
+    }
+
+    #[test]
+    fn hard_landing_overloads_the_legs() {
+        let (touchdown, _, _) = touch_down(v(0.0, -1.2), 0.0);
+        assert_eq!(touchdown.landing(), Some(Landing::Crash));
+        assert!(touchdown.stroke > MAX_STROKE);
+    }
+
+    #[test]
+    fn tips_over_on_a_slope() {
+        let (touchdown, engine, lander) = touch_down(v(0.0, -0.2), 0.5);
+        assert_eq!(touchdown.landing(), Some(Landing::Crash));
+        assert!(touchdown.tilt > MAX_TILT && touchdown.stroke < MAX_STROKE);
+        assert!(engine.get_body(lander).unwrap().angle() > MAX_TILT);
+    }
+
+    #[test]
+    fn parts_share_the_inertia() {
+        let square = positions![(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)];
+        let body = ConvexBody::still_body(2.0, &square).with_part(Part::create(
+            &square[..],
+            0.0,
+            Transform2::new(v(1.0, 0.0), 0.0),
+        ));
+        // A 2 by 1 rectangle, its center 0.5 m away from the origin.
+        let expected = 2.0 * (5.0 / 12.0 + 0.25);
+        assert!((moment_of_inertia(&body) - expected).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,104 @@
+//! Gravity as a vector field, and presets for the bodies we fly on.
+
+use crate::atmosphere::Atmosphere;
+use crate::{v, Position, Vector, MOON_GRAVITY};
+
+pub const MARS_GRAVITY: f64 = 3.721;
+pub const EARTH_GRAVITY: f64 = 9.807;
+pub const TITAN_GRAVITY: f64 = 1.352;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gravity {
+    /// Same acceleration everywhere.
+    Uniform(Vector),
+    /// Pull towards `center` of a body with gravitational parameter `mu`
+    /// (its mass times G), in m³/s².
+    PointMass { center: Position, mu: f64 },
+}
+
+impl Gravity {
+    /// Uniform gravity of `g` m/s² pulling along -y.
+    pub fn uniform(g: f64) -> Gravity {
+        Gravity::Uniform(v(0.0, -g))
+    }
+
+    /// Acceleration at `position`.
+    pub fn at(&self, position: &Position) -> Vector {
+        match self {
+            Gravity::Uniform(g) => *g,
+            Gravity::PointMass { center, mu } => {
+                let d = *center - *position;
+                let distance = d.length();
+                if distance == 0.0 {
+                    return Vector::ZERO;
+                }
+                let g = mu / (distance * distance);
+                d * g / distance
+            }
+        }
+    }
+
+    /// Magnitude of the acceleration at `position`.
+    pub fn strength_at(&self, position: &Position) -> f64 {
+        self.at(position).length()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Planet {
+    Moon,
+    Mars,
+    Earth,
+    Titan,
+}
+
+impl Planet {
+    /// Surface gravity, uniform over the flying area.
+    pub fn gravity(&self) -> Gravity {
+        Gravity::uniform(match self {
+            Planet::Moon => MOON_GRAVITY,
+            Planet::Mars => MARS_GRAVITY,
+            Planet::Earth => EARTH_GRAVITY,
+            Planet::Titan => TITAN_GRAVITY,
+        })
+    }
+
+    pub fn atmosphere(&self) -> Option<Atmosphere> {
+        match self {
+            Planet::Moon => None,
+            Planet::Mars => Some(Atmosphere::mars()),
+            Planet::Earth => Some(Atmosphere::earth()),
+            Planet::Titan => Some(Atmosphere::titan()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gravity::*;
+    use crate::pos;
+
+    #[test]
+    fn uniform_gravity_pulls_down() {
+        let moon = Planet::Moon.gravity();
+        assert_eq!(moon.at(&pos(3.0, 50.0)), v(0.0, -MOON_GRAVITY));
+        assert_eq!(moon.at(&pos(-80.0, 1.0)), v(0.0, -MOON_GRAVITY));
+        assert_eq!(
+            Planet::Mars.gravity().strength_at(&pos(0.0, 0.0)),
+            MARS_GRAVITY
+        );
+        assert!(Planet::Moon.atmosphere().is_none());
+    }
+
+    #[test]
+    fn point_mass_follows_inverse_square() {
+        let gravity = Gravity::PointMass {
+            center: pos(0.0, 0.0),
+            mu: 400.0,
+        };
+        assert_eq!(gravity.at(&pos(10.0, 0.0)), v(-4.0, 0.0));
+        assert_eq!(gravity.at(&pos(0.0, -20.0)), v(0.0, 1.0));
+        assert_eq!(gravity.strength_at(&pos(6.0, 8.0)), 4.0);
+        assert_eq!(gravity.at(&pos(0.0, 0.0)), v(0.0, 0.0));
+    }
+}
@@ -0,0 +1,360 @@
+//! Maps physical inputs, from the keyboard or from controllers, onto
+//! the abstract actions the game understands.
+
+use piston_window::{Button, ButtonState, GenericEvent, Key};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::{fmt, fs, io};
+
+/// Bindings used when there's no configuration file around.
+const DEFAULT_BINDINGS: &str = include_str!("../controls.toml");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Thrust,
+    /// The lander has no attitude control yet, so rotating fires
+    /// the lateral thrusters.
+    RotateLeft,
+    RotateRight,
+    ThrottleUp,
+    ThrottleDown,
+    Pause,
+    /// Toggles the autopilot.
+    Assist,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Input {
+    Key(Key),
+    ControllerButton(u8),
+    /// Axis past `threshold`. Negative thresholds are past it
+    /// when the axis goes below them.
+    ControllerAxis {
+        axis: u8,
+        threshold: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Binding {
+    pub action: Action,
+    #[serde(flatten)]
+    pub input: Input,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "can't read bindings: {}", e),
+            ConfigError::Parse(e) => write!(f, "invalid bindings: {}", e),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(rename = "binding", default)]
+    bindings: Vec<Binding>,
+}
+
+/// Translates input events into action presses and releases, and
+/// keeps track of which actions are being held.
+#[derive(Debug)]
+pub struct InputMap {
+    bindings: Vec<Binding>,
+    /// Last known position of each axis, by controller id and axis.
+    axes: HashMap<(u32, u8), f64>,
+    /// Indices of the bindings whose input is currently held.
+    held: HashSet<usize>,
+}
+
+fn past_threshold(position: f64, threshold: f64) -> bool {
+    if threshold < 0.0 {
+        position <= threshold
+    } else {
+        position >= threshold
+    }
+}
+
+impl InputMap {
+    pub fn create(bindings: Vec<Binding>) -> InputMap {
+        InputMap {
+            bindings,
+            axes: HashMap::new(),
+            held: HashSet::new(),
+        }
+    }
+
+    pub fn parse(config: &str) -> Result<InputMap, ConfigError> {
+        let config: Config = toml::from_str(config).map_err(ConfigError::Parse)?;
+        Ok(InputMap::create(config.bindings))
+    }
+
+    pub fn load(path: &str) -> Result<InputMap, ConfigError> {
+        let config = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        InputMap::parse(&config)
+    }
+
+    /// Loads bindings from `path`, falling back to the default ones
+    /// when the file is missing or broken.
+    pub fn load_or_default(path: &str) -> InputMap {
+        match InputMap::load(path) {
+            Ok(map) => map,
+            Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                InputMap::parse(DEFAULT_BINDINGS).unwrap()
+            }
+            Err(e) => {
+                eprintln!("{}, using default bindings", e);
+                InputMap::parse(DEFAULT_BINDINGS).unwrap()
+            }
+        }
+    }
+
+    /// Whether any of the inputs bound to `action` is held.
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.iter().any(|&i| self.bindings[i].action == action)
+    }
+
+    /// Forgets about everything being held, e.g. when the window loses
+    /// focus and release events won't reach us.
+    pub fn release_all(&mut self) {
+        self.held.clear();
+        self.axes.clear();
+    }
+
+    /// Returns the actions pressed or released by `event`. Inputs
+    /// without a binding are ignored.
+    pub fn translate<E: GenericEvent>(&mut self, event: &E) -> Vec<(Action, ButtonState)> {
+        let changes: Vec<(usize, ButtonState)> = if let Some(args) = event.button_args() {
+            let input = match args.button {
+                Button::Keyboard(key) => Input::Key(key),
+                Button::Controller(button) => Input::ControllerButton(button.button),
+                _ => return vec![],
+            };
+            self.bindings
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.input == input)
+                .map(|(i, _)| (i, args.state))
+                .collect()
+        } else if let Some(args) = event.controller_axis_args() {
+            let previous = self
+                .axes
+                .insert((args.id, args.axis), args.position)
+                .unwrap_or(0.0);
+            self.bindings
+                .iter()
+                .enumerate()
+                .filter_map(|(i, b)| match b.input {
+                    Input::ControllerAxis { axis, threshold } if axis == args.axis => {
+                        let was = past_threshold(previous, threshold);
+                        let is = past_threshold(args.position, threshold);
+                        match (was, is) {
+                            (false, true) => Some((i, ButtonState::Press)),
+                            (true, false) => Some((i, ButtonState::Release)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                })
+                .collect()
+        } else {
+            return vec![];
+        };
+
+        // Key repeats and releases of something that wasn't held
+        // (e.g. pressed before gaining focus) aren't reported.
+        changes
+            .into_iter()
+            .filter(|&(i, state)| match state {
+                ButtonState::Press => self.held.insert(i),
+                ButtonState::Release => self.held.remove(&i),
+            })
+            .map(|(i, state)| (self.bindings[i].action, state))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::input::*;
+    use piston_window::{ButtonArgs, ControllerAxisArgs, ControllerButton, Event, Input as Ev};
+
+    fn button(state: ButtonState, button: Button) -> Event {
+        Event::Input(
+            Ev::Button(ButtonArgs {
+                state,
+                button,
+                scancode: None,
+            }),
+            None,
+        )
+    }
+
+    fn press(b: Button) -> Event {
+        button(ButtonState::Press, b)
+    }
+
+    fn release(b: Button) -> Event {
+        button(ButtonState::Release, b)
+    }
+
+    fn axis(axis: u8, position: f64) -> Event {
+        controller_axis(0, axis, position)
+    }
+
+    fn controller_axis(id: u32, axis: u8, position: f64) -> Event {
+        Event::Input(
+            Ev::Move(piston_window::Motion::ControllerAxis(ControllerAxisArgs {
+                id,
+                axis,
+                position,
+            })),
+            None,
+        )
+    }
+
+    #[test]
+    fn default_bindings_parse() {
+        let map = InputMap::parse(DEFAULT_BINDINGS).unwrap();
+        assert!(map.bindings.contains(&Binding {
+            action: Action::Thrust,
+            input: Input::Key(Key::Down)
+        }));
+    }
+
+    #[test]
+    fn keys_and_buttons_map_to_actions() {
+        let mut map = InputMap::parse(
+            r#"
+            [[binding]]
+            action = "thrust"
+            key = "W"
+
+            [[binding]]
+            action = "pause"
+            controller_button = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            map.translate(&press(Button::Keyboard(Key::W))),
+            [(Action::Thrust, ButtonState::Press)]
+        );
+        assert_eq!(
+            map.translate(&press(Button::Controller(ControllerButton {
+                id: 1,
+                button: 3
+            }))),
+            [(Action::Pause, ButtonState::Press)]
+        );
+        assert_eq!(map.translate(&press(Button::Keyboard(Key::Down))), []);
+    }
+
+    #[test]
+    fn axes_press_and_release_on_threshold() {
+        let mut map = InputMap::parse(
+            r#"
+            [[binding]]
+            action = "rotate_left"
+            controller_axis = { axis = 0, threshold = -0.5 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(map.translate(&axis(0, -0.3)), []);
+        assert_eq!(
+            map.translate(&axis(0, -0.8)),
+            [(Action::RotateLeft, ButtonState::Press)]
+        );
+        assert_eq!(map.translate(&axis(0, -0.9)), []);
+        assert_eq!(map.translate(&axis(1, 0.0)), []);
+        assert_eq!(
+            map.translate(&axis(0, 0.0)),
+            [(Action::RotateLeft, ButtonState::Release)]
+        );
+    }
+
+    #[test]
+    fn controllers_have_axes_of_their_own() {
+        let mut map = InputMap::parse(
+            r#"
+            [[binding]]
+            action = "rotate_left"
+            controller_axis = { axis = 0, threshold = -0.5 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            map.translate(&controller_axis(0, 0, -0.8)),
+            [(Action::RotateLeft, ButtonState::Press)]
+        );
+        // The other controller's stick was never past the threshold.
+        assert_eq!(map.translate(&controller_axis(1, 0, -0.3)), []);
+        assert!(map.is_held(Action::RotateLeft));
+    }
+
+    #[test]
+    fn held_actions_survive_key_repeat() {
+        let mut map = InputMap::parse(DEFAULT_BINDINGS).unwrap();
+        let down = Button::Keyboard(Key::Down);
+        assert_eq!(
+            map.translate(&press(down)),
+            [(Action::Thrust, ButtonState::Press)]
+        );
+        assert_eq!(map.translate(&press(down)), []);
+        assert!(map.is_held(Action::Thrust));
+        assert_eq!(
+            map.translate(&release(down)),
+            [(Action::Thrust, ButtonState::Release)]
+        );
+        assert!(!map.is_held(Action::Thrust));
+        assert_eq!(map.translate(&release(down)), []);
+        assert!(!map.is_held(Action::Thrust));
+    }
+
+    #[test]
+    fn action_held_while_any_binding_is_held() {
+        let mut map = InputMap::parse(DEFAULT_BINDINGS).unwrap();
+        let key = Button::Keyboard(Key::Down);
+        let pad = Button::Controller(ControllerButton { id: 0, button: 0 });
+        map.translate(&press(key));
+        map.translate(&press(pad));
+        map.translate(&release(key));
+        assert!(map.is_held(Action::Thrust));
+        map.translate(&release(pad));
+        assert!(!map.is_held(Action::Thrust));
+    }
+
+    #[test]
+    fn release_all_clears_held_actions() {
+        let mut map = InputMap::parse(DEFAULT_BINDINGS).unwrap();
+        map.translate(&press(Button::Keyboard(Key::Down)));
+        map.translate(&axis(0, -1.0));
+        assert!(map.is_held(Action::Thrust));
+        assert!(map.is_held(Action::RotateLeft));
+        map.release_all();
+        assert!(!map.is_held(Action::Thrust));
+        assert!(!map.is_held(Action::RotateLeft));
+        assert!(!map.is_held(Action::RotateRight));
+    }
+
+    #[test]
+    fn invalid_bindings_are_reported() {
+        assert!(matches!(
+            InputMap::parse("[[binding]]\naction = \"jump\"\nkey = \"Space\""),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            InputMap::load("does/not/exist.toml"),
+            Err(ConfigError::Io(_))
+        ));
+    }
+}
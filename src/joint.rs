@@ -0,0 +1,524 @@
+//! Joints connecting pairs of bodies. Hard joints are solved a few times per
+//! tick, so that chains of them settle: first on velocities, cancelling the
+//! relative motion they forbid, then on positions, removing the drift left.
+//! Springs pull with forces instead, applied along with gravity.
+//! Anchors off the origin of a body turn it as they pull on it.
+
+use crate::gear::moment_of_inertia;
+use crate::{v, BodyId, ConvexBody, Engine, Position, Vector};
+
+/// Passes over all joints per tick.
+const SOLVER_ITERATIONS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JointKind {
+    /// Keeps the anchors `length` apart. A `rope` only stops them
+    /// from getting further apart.
+    Distance { length: f64, rope: bool },
+    /// Pins the anchors together, letting the bodies turn around them.
+    Revolute,
+    /// Lets the second anchor slide along the unit vector `axis` from the
+    /// first one, between `min` and `max`.
+    Prismatic { axis: Vector, min: f64, max: f64 },
+    /// Pulls the anchors towards `length` apart with `stiffness` N/m,
+    /// damping their relative speed with `damping` N·s/m.
+    Spring {
+        length: f64,
+        stiffness: f64,
+        damping: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub bodies: (BodyId, BodyId),
+    /// Where the joint attaches on each body, from its origin, as if
+    /// the body had never turned.
+    pub anchors: (Vector, Vector),
+    pub kind: JointKind,
+}
+
+impl Joint {
+    pub fn create(bodies: (BodyId, BodyId), anchors: (Vector, Vector), kind: JointKind) -> Joint {
+        Joint {
+            bodies,
+            anchors,
+            kind,
+        }
+    }
+
+    /// Creates a joint attached on both `bodies` at `anchor`, where they
+    /// are now in `engine`. Returns `None` if one of them isn't there.
+    pub fn at(
+        engine: &Engine,
+        bodies: (BodyId, BodyId),
+        anchor: &Position,
+        kind: JointKind,
+    ) -> Option<Joint> {
+        let offset = |id| {
+            let body = engine.get_body(id)?;
+            Some(body.transform().inverse().apply(*anchor))
+        };
+        Some(Joint::create(
+            bodies,
+            (offset(bodies.0)?, offset(bodies.1)?),
+            kind,
+        ))
+    }
+}
+
+/// Fixed and sleeping bodies don't give way to joints.
+fn inverse_mass(body: &ConvexBody) -> f64 {
+    if !body.moving() || body.mass <= 0.0 {
+        0.0
+    } else {
+        1.0 / body.mass
+    }
+}
+
+/// Same as `inverse_mass`, for turning.
+fn inverse_inertia(body: &ConvexBody) -> f64 {
+    match moment_of_inertia(body) {
+        inertia if body.moving() && inertia > 0.0 => 1.0 / inertia,
+        _ => 0.0,
+    }
+}
+
+fn anchor(body: &ConvexBody, offset: &Vector) -> Position {
+    body.transform().apply(*offset)
+}
+
+/// Velocity of the point `r` away from the origin of `body`.
+fn velocity_at(body: &ConvexBody, r: &Vector) -> Vector {
+    body.velocity + r.perpendicular() * body.angular_velocity
+}
+
+/// Relative motion of the anchors a hard joint forbids.
+enum Blocked {
+    /// All of it, which is this.
+    All(Vector),
+    /// Only its part along one direction, which is this.
+    Along(Vector),
+}
+
+/// How a body of a joint gives way to impulses on its anchor, `r` away
+/// from its origin.
+struct Give {
+    mass: f64,
+    inertia: f64,
+    r: Vector,
+}
+
+impl Give {
+    /// How much the anchor moves along `n` for a unit impulse along `n`.
+    fn along(&self, n: &Vector) -> f64 {
+        self.mass + self.inertia * self.r.cross(*n).powi(2)
+    }
+
+    /// How much the anchor moves, on each axis, for a unit impulse along
+    /// each axis, as the rows of a matrix.
+    fn matrix(&self) -> [[f64; 2]; 2] {
+        let Vector { x, y } = self.r;
+        let i = self.inertia;
+        [
+            [self.mass + i * y * y, -i * x * y],
+            [-i * x * y, self.mass + i * x * x],
+        ]
+    }
+}
+
+/// Impulse on the second anchor, the first one taking the opposite, that
+/// undoes the relative motion `blocked` of the anchors.
+fn impulse(blocked: &Blocked, give1: &Give, give2: &Give) -> Option<Vector> {
+    match blocked {
+        Blocked::Along(c) => {
+            let n = c.normalize();
+            let k = give1.along(&n) + give2.along(&n);
+            (k > 0.0).then(|| -*c / k)
+        }
+        Blocked::All(c) => {
+            let (m1, m2) = (give1.matrix(), give2.matrix());
+            let k = [
+                [m1[0][0] + m2[0][0], m1[0][1] + m2[0][1]],
+                [m1[1][0] + m2[1][0], m1[1][1] + m2[1][1]],
+            ];
+            let determinant = k[0][0] * k[1][1] - k[0][1] * k[1][0];
+            (determinant.abs() > f64::EPSILON).then(|| {
+                -v(k[1][1] * c.x - k[0][1] * c.y, k[0][0] * c.y - k[1][0] * c.x) / determinant
+            })
+        }
+    }
+}
+
+/// How much positions can be off before a rope or a limit counts as reached.
+const SLOP: f64 = 1e-9;
+
+/// Position correction bringing the second anchor where `kind` wants
+/// it, given `d` from the first anchor to the second one.
+fn position_correction(kind: &JointKind, d: &Vector) -> Option<Blocked> {
+    match kind {
+        JointKind::Revolute => Some(Blocked::All(*d)),
+        JointKind::Distance { length, rope } => {
+            let distance = d.length();
+            let error = distance - length;
+            if distance == 0.0 || (*rope && error <= 0.0) {
+                return None;
+            }
+            Some(Blocked::Along(*d / distance * error))
+        }
+        JointKind::Prismatic { axis, min, max } => {
+            let along = d.dot(*axis);
+            let excess = along - along.clamp(*min, *max);
+            let off = *d - *axis * along;
+            if excess == 0.0 {
+                Some(Blocked::Along(off))
+            } else {
+                Some(Blocked::All(off + *axis * excess))
+            }
+        }
+        JointKind::Spring { .. } => None,
+    }
+}
+
+/// Part of the velocity `u` of the second anchor relative to the first one
+/// that `kind` forbids, given `d` from the first anchor to the second one.
+fn velocity_correction(kind: &JointKind, d: &Vector, u: &Vector) -> Option<Blocked> {
+    match kind {
+        JointKind::Revolute => Some(Blocked::All(*u)),
+        JointKind::Distance { length, rope } => {
+            let distance = d.length();
+            if distance == 0.0 {
+                return None;
+            }
+            let n = *d / distance;
+            let separating = u.dot(n);
+            if *rope && (distance < length - SLOP || separating <= 0.0) {
+                return None;
+            }
+            Some(Blocked::Along(n * separating))
+        }
+        JointKind::Prismatic { axis, min, max } => {
+            let along = d.dot(*axis);
+            let sliding = u.dot(*axis);
+            let blocked =
+                (along >= max - SLOP && sliding > 0.0) || (along <= min + SLOP && sliding < 0.0);
+            if blocked {
+                Some(Blocked::All(*u))
+            } else {
+                Some(Blocked::Along(*u - *axis * sliding))
+            }
+        }
+        JointKind::Spring { .. } => None,
+    }
+}
+
+impl Engine {
+    pub fn add_joint(&mut self, joint: Joint) {
+        self.joints.push(joint);
+    }
+
+    /// Both bodies of a joint, if they are still there and distinct.
+    fn pair_mut(&mut self, (a, b): (BodyId, BodyId)) -> Option<(&mut ConvexBody, &mut ConvexBody)> {
+        if a.index == b.index {
+            return None;
+        }
+        let valid = |id: BodyId, slots: &[crate::Slot]| {
+            slots
+                .get(id.index as usize)
+                .is_some_and(|s| s.generation == id.generation && s.body.is_some())
+        };
+        if !valid(a, &self.slots) || !valid(b, &self.slots) {
+            return None;
+        }
+        let (low, high) = (a.index.min(b.index) as usize, a.index.max(b.index) as usize);
+        let (left, right) = self.slots.split_at_mut(high);
+        let (first, second) = (
+            left[low].body.as_mut().unwrap(),
+            right[0].body.as_mut().unwrap(),
+        );
+        if a.index < b.index {
+            Some((first, second))
+        } else {
+            Some((second, first))
+        }
+    }
+
+    /// Undoes on both bodies of `joint` the relative motion of its anchors
+    /// that `solve` finds, given the bodies, the anchors from their origins
+    /// and the vector from the first anchor to the second one. `apply`
+    /// moves a body by a change of its linear and angular motion.
+    fn solve_joint<S, A>(&mut self, joint: &Joint, solve: S, apply: A)
+    where
+        S: FnOnce(&ConvexBody, &ConvexBody, (&Vector, &Vector), &Vector) -> Option<Blocked>,
+        A: Fn(&mut ConvexBody, Vector, f64),
+    {
+        let Some((body1, body2)) = self.pair_mut(joint.bodies) else {
+            return;
+        };
+        let give = |body: &ConvexBody, offset| {
+            let (mass, inertia) = (inverse_mass(body), inverse_inertia(body));
+            let r = anchor(body, offset) - body.position();
+            Give { mass, inertia, r }
+        };
+        let (give1, give2) = (give(body1, &joint.anchors.0), give(body2, &joint.anchors.1));
+        if give1.mass + give2.mass == 0.0 {
+            return;
+        }
+        let d = (body2.position() + give2.r) - (body1.position() + give1.r);
+        let Some(p) = solve(body1, body2, (&give1.r, &give2.r), &d)
+            .and_then(|blocked| impulse(&blocked, &give1, &give2))
+        else {
+            return;
+        };
+        apply(body1, -p * give1.mass, -give1.r.cross(p) * give1.inertia);
+        apply(body2, p * give2.mass, give2.r.cross(p) * give2.inertia);
+    }
+
+    /// Adds the pull of spring joints to `accelerations`.
+    pub(crate) fn accumulate_spring_forces(&mut self) {
+        let mut accelerations = std::mem::take(&mut self.accelerations);
+        let mut angular_accelerations = std::mem::take(&mut self.angular_accelerations);
+        if self
+            .joints
+            .iter()
+            .any(|j| matches!(j.kind, JointKind::Spring { .. }))
+        {
+            accelerations.resize(self.slots.len(), v(0.0, 0.0));
+            angular_accelerations.resize(self.slots.len(), 0.0);
+        }
+        self.joints.iter().for_each(|joint| {
+            let JointKind::Spring {
+                length,
+                stiffness,
+                damping,
+            } = joint.kind
+            else {
+                return;
+            };
+            let (Some(body1), Some(body2)) =
+                (self.get_body(joint.bodies.0), self.get_body(joint.bodies.1))
+            else {
+                return;
+            };
+            let (a1, a2) = (
+                anchor(body1, &joint.anchors.0),
+                anchor(body2, &joint.anchors.1),
+            );
+            let d = a2 - a1;
+            let distance = d.length();
+            if distance == 0.0 {
+                return;
+            }
+            let n = d / distance;
+            let (r1, r2) = (a1 - body1.position(), a2 - body2.position());
+            let closing = (velocity_at(body2, &r2) - velocity_at(body1, &r1)).dot(n);
+            // Force on the second body, along n.
+            let force = n * (-stiffness * (distance - length) - damping * closing);
+            let (i1, i2) = (joint.bodies.0.index as usize, joint.bodies.1.index as usize);
+            accelerations[i1] -= force * inverse_mass(body1);
+            accelerations[i2] += force * inverse_mass(body2);
+            angular_accelerations[i1] -= r1.cross(force) * inverse_inertia(body1);
+            angular_accelerations[i2] += r2.cross(force) * inverse_inertia(body2);
+        });
+        self.accelerations = accelerations;
+        self.angular_accelerations = angular_accelerations;
+    }
+
+    /// Moves bodies, and changes their velocities, to satisfy hard joints.
+    pub(crate) fn solve_joints(&mut self) {
+        let joints = std::mem::take(&mut self.joints);
+        for _ in 0..SOLVER_ITERATIONS {
+            joints.iter().for_each(|joint| {
+                self.solve_joint(
+                    joint,
+                    |body1, body2, (r1, r2), d| {
+                        let u = velocity_at(body2, r2) - velocity_at(body1, r1);
+                        velocity_correction(&joint.kind, d, &u)
+                    },
+                    |body, linear, angular| {
+                        body.velocity += linear;
+                        body.angular_velocity += angular;
+                    },
+                );
+            });
+        }
+        for _ in 0..SOLVER_ITERATIONS {
+            joints.iter().for_each(|joint| {
+                self.solve_joint(
+                    joint,
+                    |_, _, _, d| position_correction(&joint.kind, d),
+                    |body, linear, angular| {
+                        body.translate(linear);
+                        if angular != 0.0 {
+                            body.rotate(angular);
+                        }
+                    },
+                );
+            });
+        }
+        self.joints = joints;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::joint::*;
+    use crate::{pos, square};
+
+    fn center(engine: &Engine, id: BodyId) -> Position {
+        engine.get_body(id).unwrap().position()
+    }
+
+    #[test]
+    fn revolute_joint_holds_body() {
+        let mut engine = Engine::create(10.0);
+        let ceiling = engine.add_body(ConvexBody::fixed_body(&[
+            pos(-1.0, 10.0),
+            pos(1.0, 10.0),
+            pos(1.0, 11.0),
+            pos(-1.0, 11.0),
+        ]));
+        let cargo = engine.add_body(square(1.0, pos(0.0, 9.5), 1.0));
+        let joint = Joint::at(
+            &engine,
+            (ceiling, cargo),
+            &pos(0.0, 10.0),
+            JointKind::Revolute,
+        );
+        engine.add_joint(joint.unwrap());
+
+        (0..100).for_each(|_| engine.tick(0.02));
+        let c = center(&engine, cargo);
+        assert!(c.x.abs() < 1e-9 && (c.y - 9.5).abs() < 1e-9);
+        assert!(engine.get_body(cargo).unwrap().velocity.y.abs() < 1e-9);
+    }
+
+    #[test]
+    fn revolute_joint_lets_body_swing() {
+        let mut engine = Engine::create(10.0);
+        let hook = engine.add_body(ConvexBody::fixed_body(&[pos(0.0, 0.0)]));
+        let bar = engine.add_body(ConvexBody::still_body(
+            1.0,
+            &[pos(0.0, -0.1), pos(2.0, -0.1), pos(2.0, 0.1), pos(0.0, 0.1)],
+        ));
+        let joint = Joint::at(&engine, (hook, bar), &pos(0.0, 0.0), JointKind::Revolute);
+        engine.add_joint(joint.unwrap());
+
+        let mut lowest = f64::MAX;
+        (0..100).for_each(|_| {
+            engine.tick(0.01);
+            let body = engine.get_body(bar).unwrap();
+            // The pinned end stays on the hook, the bar turning around it.
+            assert!(body.transform().apply(v(-1.0, 0.0)).length() < 1e-6);
+            assert!((body.position().length() - 1.0).abs() < 1e-6);
+            lowest = lowest.min(body.position().y);
+        });
+        // Swung down to hang below the hook.
+        assert!(lowest < -0.95);
+        assert!(engine.get_body(bar).unwrap().angle() < -1.0);
+    }
+
+    #[test]
+    fn tether_swings_at_its_length() {
+        let mut engine = Engine::create(10.0);
+        let hook = engine.add_body(ConvexBody::fixed_body(&[pos(0.0, 0.0)]));
+        let cargo = engine.add_body(square(1.0, pos(3.0, 0.0), 1.0));
+        engine.add_joint(Joint::create(
+            (hook, cargo),
+            (v(0.0, 0.0), v(0.0, 0.0)),
+            JointKind::Distance {
+                length: 3.0,
+                rope: true,
+            },
+        ));
+
+        let mut lowest = f64::MAX;
+        (0..200).for_each(|_| {
+            engine.tick(0.01);
+            let c = center(&engine, cargo);
+            assert!(c.length() < 3.0 + 1e-6);
+            lowest = lowest.min(c.y);
+        });
+        // Swung down to the bottom of the arc.
+        assert!(lowest < -2.9);
+    }
+
+    #[test]
+    fn rope_goes_slack() {
+        let mut engine = Engine::create(0.0);
+        let a = engine.add_body(square(1.0, pos(0.0, 0.0), 1.0));
+        let b = engine.add_body(square(1.0, pos(1.0, 0.0), 1.0));
+        engine.add_joint(Joint::create(
+            (a, b),
+            (v(0.0, 0.0), v(0.0, 0.0)),
+            JointKind::Distance {
+                length: 3.0,
+                rope: true,
+            },
+        ));
+        engine.get_body_mut(b).unwrap().velocity = v(1.0, 0.0);
+        (0..100).for_each(|_| engine.tick(0.01));
+        assert!((center(&engine, b).x - 2.0).abs() < 1e-9);
+        assert_eq!(center(&engine, a), pos(0.0, 0.0));
+
+        // Once taut, both bodies move together, sharing momentum.
+        (0..300).for_each(|_| engine.tick(0.01));
+        let (ca, cb) = (center(&engine, a), center(&engine, b));
+        assert!((cb.x - ca.x - 3.0).abs() < 1e-6);
+        assert!((engine.get_body(a).unwrap().velocity.x - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn prismatic_joint_slides_within_limits() {
+        let mut engine = Engine::create(10.0);
+        let rail = engine.add_body(ConvexBody::fixed_body(&[pos(0.0, 0.0)]));
+        let slider = engine.add_body(square(1.0, pos(0.0, 0.0), 1.0));
+        let diagonal = v(0.5_f64.sqrt(), -(0.5_f64.sqrt()));
+        engine.add_joint(Joint::create(
+            (rail, slider),
+            (v(0.0, 0.0), v(0.0, 0.0)),
+            JointKind::Prismatic {
+                axis: diagonal,
+                min: 0.0,
+                max: 2.0,
+            },
+        ));
+
+        (0..200).for_each(|_| engine.tick(0.01));
+        let c = center(&engine, slider);
+        assert!((c.x + c.y).abs() < 1e-9);
+        assert!((c.dot(diagonal) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn spring_settles_under_load() {
+        let mut engine = Engine::create(10.0);
+        // Sleeping would stop it a hair away from its rest length.
+        engine.sleep_energy = 0.0;
+        let ceiling = engine.add_body(ConvexBody::fixed_body(&[pos(0.0, 0.0)]));
+        let cargo = engine.add_body(square(1.0, pos(0.0, -1.0), 1.0));
+        engine.add_joint(Joint::create(
+            (ceiling, cargo),
+            (v(0.0, 0.0), v(0.0, 0.0)),
+            JointKind::Spring {
+                length: 1.0,
+                stiffness: 100.0,
+                damping: 5.0,
+            },
+        ));
+
+        (0..1000).for_each(|_| engine.tick(0.01));
+        // Stretched by m·g / k.
+        assert!((center(&engine, cargo).y + 1.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn joints_go_with_their_bodies() {
+        let mut engine = Engine::create(0.0);
+        let a = engine.add_body(square(1.0, pos(0.0, 0.0), 1.0));
+        let b = engine.add_body(square(1.0, pos(1.0, 0.0), 1.0));
+        engine.add_joint(Joint::at(&engine, (a, b), &pos(0.5, 0.0), JointKind::Revolute).unwrap());
+        engine.remove_body(b);
+        assert!(engine.joints.is_empty());
+    }
+}
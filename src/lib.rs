@@ -0,0 +1,1606 @@
+//! Simulation of the lander: physics engine, terrain and landing rules.
+//! It runs without a window; the game itself lives in `main.rs`.
+
+use atmosphere::Atmosphere;
+use gear::Leg;
+use gravity::Gravity;
+use itertools::Itertools;
+use joint::Joint;
+use rand::Rng;
+use std::cell::{Cell, OnceCell};
+use std::fmt;
+use std::ops::{Add, Mul};
+use std::sync::Arc;
+
+pub use math::{Transform2, Vec2};
+
+/// Vectors and positions are both `Vec2`, the names only tell them apart.
+pub type Vector = Vec2;
+pub type Position = Vec2;
+
+pub fn v(x: f64, y: f64) -> Vector {
+    Vec2::new(x, y)
+}
+
+pub fn pos(x: f64, y: f64) -> Position {
+    Vec2::new(x, y)
+}
+
+#[macro_export]
+macro_rules! positions {
+    ($(($x:expr, $y:expr)),*) => {
+        [$($crate::Position{ x: $x, y: $y }),*]
+    }
+}
+
+/// Square body with sides of `side` meters around `center`, for tests.
+#[cfg(test)]
+pub(crate) fn square(mass: f64, center: Position, side: f64) -> ConvexBody {
+    let half = side / 2.0;
+    ConvexBody::still_body(
+        mass,
+        &[
+            center + v(-half, -half),
+            center + v(half, -half),
+            center + v(half, half),
+            center + v(-half, half),
+        ],
+    )
+}
+
+/// Collision categories. Each body belongs to the categories in its
+/// `layer` and only touches bodies whose `layer` is in its `mask`.
+pub mod layer {
+    pub const DEFAULT: u32 = 1;
+    pub const TERRAIN: u32 = 1 << 1;
+    pub const LANDER: u32 = 1 << 2;
+    pub const DEBRIS: u32 = 1 << 3;
+    pub const SENSOR: u32 = 1 << 4;
+    pub const ALL: u32 = u32::MAX;
+}
+
+pub mod atmosphere;
+pub mod autopilot;
+pub mod batch;
+pub mod collision;
+pub mod crater;
+pub mod debris;
+pub mod env;
+pub mod gear;
+pub mod gravity;
+pub mod joint;
+pub mod math;
+pub mod orbit;
+pub mod shape;
+pub mod sleep;
+
+#[derive(Debug)]
+pub struct ConvexBody {
+    pub mass: f64,
+    /// Vertices around the body's origin. Bodies of the same shape can
+    /// share them.
+    shape: Arc<[Position]>,
+    /// Where the body's origin is, and how far it's turned from `shape`.
+    transform: Transform2,
+    /// `shape` placed by `transform`.
+    mesh: MeshCache,
+    /// How far the body reaches out of `shape`, which makes circles out
+    /// of single vertices and capsules out of segments.
+    radius: f64,
+    /// Moment of inertia per kilogram, see `gear::moment_of_inertia`.
+    unit_inertia: f64,
+    pub acceleration: Vector,
+    pub velocity: Vector,
+    pub fixed: bool,
+    /// Categories the body belongs to, see `layer`.
+    pub layer: u32,
+    /// Categories the body can touch.
+    pub mask: u32,
+    /// Sensors report overlaps in `Engine::overlaps` instead of collisions.
+    pub sensor: bool,
+    pub angular_velocity: f64,
+    /// More shapes the body is made of, moving with it. Contacts tell
+    /// the body's own shape as part 0 and these as the following ones.
+    parts: Vec<Part>,
+    /// Spring-damper legs pushing back on fixed bodies their tips sink into.
+    pub legs: Vec<Leg>,
+    /// Sleeping bodies are left out of the simulation until something
+    /// touches them or pushes on them, see `sleep`.
+    pub sleeping: bool,
+    /// How long the body has been slow enough to sleep, in seconds.
+    idle: f64,
+}
+
+impl ConvexBody {
+    /// Creates a body out of the world-space vertices of `mesh`, with its
+    /// origin at its center of mass. The mesh is taken as it is, see
+    /// `from_mesh` for checking it.
+    pub fn still_body(m: f64, mesh: &[Position]) -> ConvexBody {
+        let center = shape::center_of_mass(mesh);
+        let shape = mesh.iter().map(|p| *p - center).collect();
+        ConvexBody::from_shape(m, shape, Transform2::new(center, 0.0))
+    }
+
+    /// Same as `still_body`, for meshes drawn by hand: fails unless
+    /// `shape::validate` takes `mesh` as a convex polygon, which it puts
+    /// counterclockwise.
+    pub fn from_mesh(m: f64, mesh: &[Position]) -> Result<ConvexBody, shape::ShapeError> {
+        shape::validate(mesh).map(|mesh| ConvexBody::still_body(m, &mesh))
+    }
+
+    /// Creates a circle of `radius` around `center`.
+    pub fn circle(m: f64, center: Position, radius: f64) -> ConvexBody {
+        let mut body = ConvexBody {
+            radius,
+            ..ConvexBody::still_body(m, &[center])
+        };
+        body.update_inertia();
+        body
+    }
+
+    /// Creates a capsule: the points within `radius` of the segment
+    /// between `a` and `b`.
+    pub fn capsule(m: f64, a: Position, b: Position, radius: f64) -> ConvexBody {
+        let mut body = ConvexBody {
+            radius,
+            ..ConvexBody::still_body(m, &[a, b])
+        };
+        body.update_inertia();
+        body
+    }
+
+    pub fn segment(m: f64, a: Position, b: Position) -> ConvexBody {
+        ConvexBody::still_body(m, &[a, b])
+    }
+
+    pub fn fixed_body(mesh: &[Position]) -> ConvexBody {
+        ConvexBody {
+            fixed: true,
+            layer: layer::TERRAIN,
+            ..ConvexBody::still_body(0.0, mesh)
+        }
+    }
+
+    /// Creates a body of `shape`, given around the body's origin, placed
+    /// by `transform`.
+    pub fn from_shape(m: f64, shape: Arc<[Position]>, transform: Transform2) -> ConvexBody {
+        let mut body = ConvexBody {
+            mass: m,
+            shape,
+            transform,
+            mesh: MeshCache::default(),
+            radius: 0.0,
+            unit_inertia: 0.0,
+            acceleration: v(0.0, 0.0),
+            velocity: v(0.0, 0.0),
+            fixed: false,
+            layer: layer::DEFAULT,
+            mask: layer::ALL,
+            sensor: false,
+            angular_velocity: 0.0,
+            parts: vec![],
+            legs: vec![],
+            sleeping: false,
+            idle: 0.0,
+        };
+        body.update_inertia();
+        body
+    }
+
+    /// Works `unit_inertia` out again once the shapes of the body change.
+    fn update_inertia(&mut self) {
+        self.unit_inertia = gear::unit_inertia(&self.shape, self.radius, &self.parts);
+    }
+
+    pub fn shape(&self) -> &Arc<[Position]> {
+        &self.shape
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// What the body's own shape looks like to collision tests.
+    pub fn primitive(&self) -> collision::Primitive<'_> {
+        collision::Primitive::of(self.mesh(), self.radius)
+    }
+
+    /// What each part of the body looks like to collision tests, its own
+    /// shape first.
+    pub fn primitives(&self) -> impl Iterator<Item = collision::Primitive<'_>> + Clone {
+        std::iter::once(self.primitive()).chain(
+            self.parts
+                .iter()
+                .map(|part| collision::Primitive::of(part.mesh(&self.transform), part.radius)),
+        )
+    }
+
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    pub fn transform(&self) -> Transform2 {
+        self.transform
+    }
+
+    /// World-space position of the body's origin.
+    pub fn position(&self) -> Position {
+        self.transform.translation
+    }
+
+    /// Rotation from `shape`, counterclockwise, in radians.
+    pub fn angle(&self) -> f64 {
+        self.transform.rotation
+    }
+
+    /// World-space vertices of the body.
+    pub fn mesh(&self) -> &[Position] {
+        self.mesh.get(&self.shape, &self.transform)
+    }
+
+    pub fn set_transform(&mut self, transform: Transform2) {
+        self.transform = transform;
+        self.mesh.invalidate();
+        self.parts
+            .iter_mut()
+            .for_each(|part| part.mesh.invalidate());
+    }
+
+    pub fn translate(&mut self, displacement: Vector) {
+        self.set_transform(Transform2::new(
+            self.transform.translation + displacement,
+            self.transform.rotation,
+        ));
+    }
+
+    pub fn apply_force(&mut self, fx: f64, fy: f64) {
+        if fx != 0.0 || fy != 0.0 {
+            self.wake();
+        }
+        self.acceleration += v(fx, fy) / self.mass;
+    }
+
+    /// Sets the force on the body. Wakes it up only if the force changes.
+    pub fn set_resulting_force(&mut self, fx: f64, fy: f64) {
+        let acceleration = v(fx, fy) / self.mass;
+        if acceleration != self.acceleration {
+            self.wake();
+        }
+        self.acceleration = acceleration;
+    }
+
+    pub fn wake(&mut self) {
+        self.sleeping = false;
+        self.idle = 0.0;
+    }
+
+    /// Whether the body moves in the next tick.
+    fn moving(&self) -> bool {
+        !self.fixed && !self.sleeping
+    }
+
+    pub fn with_layers(mut self, layer: u32, mask: u32) -> Self {
+        self.layer = layer;
+        self.mask = mask;
+        self
+    }
+
+    pub fn sensor(mut self) -> Self {
+        self.sensor = true;
+        self
+    }
+
+    pub fn with_legs(mut self, legs: Vec<Leg>) -> Self {
+        self.legs = legs;
+        self
+    }
+
+    /// Adds `part` to the shapes the body is made of. Bodies have up to
+    /// `MAX_PARTS` parts, their own shape included.
+    pub fn with_part(mut self, part: Part) -> Self {
+        assert!(self.parts.len() + 1 < MAX_PARTS, "too many parts");
+        self.parts.push(part);
+        self.update_inertia();
+        self
+    }
+
+    /// Turns the body by `angle` radians, counterclockwise, around its
+    /// origin.
+    pub fn rotate(&mut self, angle: f64) {
+        self.set_transform(Transform2::new(
+            self.transform.translation,
+            self.transform.rotation + angle,
+        ));
+    }
+
+    /// Whether the engine should check `self` against `other`. Fixed
+    /// bodies never move, so two of them are never checked.
+    fn interacts_with(&self, other: &ConvexBody) -> bool {
+        !(self.fixed && other.fixed) && self.layer & other.mask != 0 && other.layer & self.mask != 0
+    }
+}
+
+/// Most parts a body can have, as contacts tell them in a `u32`.
+pub const MAX_PARTS: usize = 32;
+
+/// Shape a body is made of besides its own, see `ConvexBody::with_part`.
+#[derive(Debug)]
+pub struct Part {
+    shape: Arc<[Position]>,
+    radius: f64,
+    /// Where the part sits on the body, from the body's origin, as if the
+    /// body had never turned.
+    offset: Transform2,
+    /// `shape` placed on the body.
+    mesh: MeshCache,
+}
+
+impl Part {
+    /// Creates a part of `shape`, grown by `radius` like circles and
+    /// capsules are, placed by `offset`.
+    pub fn create(shape: impl Into<Arc<[Position]>>, radius: f64, offset: Transform2) -> Part {
+        Part {
+            shape: shape.into(),
+            radius,
+            offset,
+            mesh: MeshCache::default(),
+        }
+    }
+
+    pub fn shape(&self) -> &Arc<[Position]> {
+        &self.shape
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn offset(&self) -> Transform2 {
+        self.offset
+    }
+
+    /// World-space vertices of the part on a body placed by `body`.
+    fn mesh(&self, body: &Transform2) -> &[Position] {
+        self.mesh.get(&self.shape, &self.offset.then(body))
+    }
+}
+
+/// World-space vertices of a shape, worked out the first time they're
+/// asked for after the shape moves. The buffer is kept from one move to
+/// the next, so moving bodies don't allocate on every tick.
+#[derive(Default)]
+struct MeshCache {
+    mesh: OnceCell<Vec<Position>>,
+    /// Buffer of the mesh from before the last move, to refill.
+    spare: Cell<Vec<Position>>,
+}
+
+impl MeshCache {
+    fn get(&self, shape: &[Position], place: &Transform2) -> &[Position] {
+        self.mesh.get_or_init(|| {
+            let mut mesh = self.spare.take();
+            mesh.clear();
+            mesh.extend(shape.iter().map(|p| place.apply(*p)));
+            mesh
+        })
+    }
+
+    /// Marks the mesh out of date, keeping its buffer.
+    fn invalidate(&mut self) {
+        if let Some(mesh) = self.mesh.take() {
+            self.spare.set(mesh);
+        }
+    }
+}
+
+impl fmt::Debug for MeshCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("MeshCache").field(&self.mesh.get()).finish()
+    }
+}
+
+/// Handle to a body of an `Engine`. Handles of removed bodies are never
+/// handed out again, so they can't end up pointing at a newer body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BodyId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContactPhase {
+    /// The bodies touched for the first time in the last tick.
+    Begin,
+    /// The bodies were already touching before the last tick.
+    Persist,
+    /// The bodies stopped touching in the last tick.
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactEvent {
+    pub bodies: (BodyId, BodyId),
+    pub phase: ContactPhase,
+    /// Whether one of the bodies is a sensor.
+    pub sensor: bool,
+    /// Velocity of the second body relative to the first one.
+    pub relative_velocity: Vector,
+    /// Magnitude of the impulse that would stop the bodies moving
+    /// relative to each other.
+    pub impulse: f64,
+    /// Parts of each body in contact, bit `i` standing for part `i`.
+    /// None for contacts that ended.
+    pub parts: Option<(u32, u32)>,
+}
+
+impl ContactEvent {
+    /// Returns the body `id` is in contact with, if `id` takes part
+    /// in this contact.
+    pub fn other(&self, id: BodyId) -> Option<BodyId> {
+        match self.bodies {
+            (a, b) if a == id => Some(b),
+            (a, b) if b == id => Some(a),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    generation: u32,
+    body: Option<ConvexBody>,
+}
+
+pub struct Engine {
+    slots: Vec<Slot>,
+    /// Indices of empty slots, reused by `add_body`.
+    free: Vec<u32>,
+    pub gravity: Gravity,
+    /// Air around the bodies. There is none by default, as on the Moon.
+    pub atmosphere: Option<Atmosphere>,
+    pub joints: Vec<Joint>,
+    /// Acceleration springs and legs give each slot's body in the
+    /// current tick, and the angular one legs give it.
+    accelerations: Vec<Vector>,
+    angular_accelerations: Vec<f64>,
+    /// Kinetic energy per kilogram under which bodies resting on others
+    /// fall asleep, in J/kg. Zero keeps every body awake.
+    pub sleep_energy: f64,
+    islands: sleep::Islands,
+    /// Simulated seconds so far.
+    time: f64,
+    pub collisions: Vec<(BodyId, BodyId)>,
+    /// Pairs of bodies, at least one of them a sensor, overlapping
+    /// in the last tick.
+    pub overlaps: Vec<(BodyId, BodyId)>,
+    events: Vec<ContactEvent>,
+    /// Contacts of removed bodies, reported as ended by the next tick.
+    removed_contacts: Vec<ContactEvent>,
+    /// Buffers for the contacts of the tick before the last one.
+    previous_collisions: Vec<(BodyId, BodyId)>,
+    previous_overlaps: Vec<(BodyId, BodyId)>,
+}
+
+impl Engine {
+    /// Creates an engine with uniform gravity of `g` m/s² along -y.
+    pub fn create(g: f64) -> Engine {
+        Engine::create_with(Gravity::uniform(g))
+    }
+
+    pub fn create_with(gravity: Gravity) -> Engine {
+        Engine {
+            slots: vec![],
+            free: vec![],
+            gravity,
+            atmosphere: None,
+            joints: vec![],
+            accelerations: vec![],
+            angular_accelerations: vec![],
+            sleep_energy: sleep::SLEEP_ENERGY,
+            islands: sleep::Islands::default(),
+            time: 0.0,
+            collisions: vec![],
+            overlaps: vec![],
+            events: vec![],
+            removed_contacts: vec![],
+            previous_collisions: vec![],
+            previous_overlaps: vec![],
+        }
+    }
+
+    pub fn has_collisions(&self) -> bool {
+        !self.collisions.is_empty()
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Contacts that began, persisted or ended in the last tick.
+    pub fn contact_events(&self) -> &[ContactEvent] {
+        &self.events
+    }
+}
+
+/// Mass of the equivalent single body when `body1` and `body2` push
+/// on each other. Fixed bodies have infinite mass.
+/// Event of a contact between `bodies`, which are `pair`.
+fn contact_event(
+    bodies: (BodyId, BodyId),
+    pair: (&ConvexBody, &ConvexBody),
+    phase: ContactPhase,
+    sensor: bool,
+) -> ContactEvent {
+    let (body1, body2) = pair;
+    let relative_velocity = body2.velocity - body1.velocity;
+    let parts = match phase {
+        ContactPhase::End => None,
+        _ => Some(collision::touching_parts(body1, body2)),
+    };
+    ContactEvent {
+        bodies,
+        phase,
+        sensor,
+        relative_velocity,
+        impulse: reduced_mass(body1, body2) * relative_velocity.length(),
+        parts,
+    }
+}
+
+fn reduced_mass(body1: &ConvexBody, body2: &ConvexBody) -> f64 {
+    match (body1.fixed, body2.fixed) {
+        (true, true) => 0.0,
+        (true, false) => body2.mass,
+        (false, true) => body1.mass,
+        (false, false) => body1.mass * body2.mass / (body1.mass + body2.mass),
+    }
+}
+
+/// Integrates velocity `v` under acceleration `a` for `dt` seconds, for
+/// vectors as well as for single axes or angles. Returns the new velocity
+/// and the displacement.
+fn integrate<T>(v: T, a: T, dt: f64) -> (T, T)
+where
+    T: Copy + Add<Output = T> + Mul<f64, Output = T>,
+{
+    let new_v = v + (a * dt);
+    (new_v, (new_v + v) * (dt / 2.0))
+}
+
+impl Engine {
+    /// Moves `body` for `dt` seconds under its own acceleration plus
+    /// `external`, the one its surroundings give it.
+    fn update_body_position(body: &mut ConvexBody, external: Vector, dt: f64) -> bool {
+        if body.fixed {
+            return false;
+        }
+        let (velocity, displacement) = integrate(body.velocity, body.acceleration + external, dt);
+        body.velocity = velocity;
+        if displacement != Vector::ZERO {
+            body.translate(displacement);
+        }
+        displacement != Vector::ZERO
+    }
+
+    /// Turns `body` for `dt` seconds under `angular_acceleration`.
+    fn update_body_rotation(body: &mut ConvexBody, angular_acceleration: f64, dt: f64) {
+        let (w, turn) = integrate(body.angular_velocity, angular_acceleration, dt);
+        body.angular_velocity = w;
+        if turn != 0.0 {
+            body.rotate(turn);
+        }
+    }
+
+    pub fn tick(&mut self, dt: f64) {
+        self.accelerations.clear();
+        self.angular_accelerations.clear();
+        self.accumulate_spring_forces();
+        self.accumulate_leg_forces();
+        let (gravity, atmosphere, time) = (&self.gravity, &self.atmosphere, self.time);
+        let (accelerations, angular_accelerations) =
+            (&self.accelerations, &self.angular_accelerations);
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| Some((index, slot.body.as_mut()?)))
+            .filter(|(_, body)| body.moving())
+            .for_each(|(index, body)| {
+                let g = gravity.at(&body.position());
+                let drag = match atmosphere {
+                    Some(atmosphere) => atmosphere.drag(body, time, dt),
+                    None => Vector::ZERO,
+                };
+                let pushed = accelerations.get(index).copied().unwrap_or_default();
+                Self::update_body_position(body, g + drag + pushed, dt);
+                let angular_acceleration = angular_accelerations.get(index).copied();
+                if angular_acceleration.is_some() || body.angular_velocity != 0.0 {
+                    Self::update_body_rotation(body, angular_acceleration.unwrap_or(0.0), dt);
+                }
+                // Gravity changing along the way, as around a point mass,
+                // is averaged into the velocity like velocity Verlet does.
+                // Without it orbits gain energy at every turn.
+                let new_g = gravity.at(&body.position());
+                body.velocity += (new_g - g) / 2.0 * dt;
+            });
+        if !self.joints.is_empty() {
+            self.solve_joints();
+        }
+        self.time += dt;
+        // The contacts of the previous tick are kept to tell which ones
+        // are new, and the buffers are swapped to keep their allocations
+        // from one tick to the next.
+        std::mem::swap(&mut self.collisions, &mut self.previous_collisions);
+        std::mem::swap(&mut self.overlaps, &mut self.previous_overlaps);
+        let mut collisions = std::mem::take(&mut self.collisions);
+        let mut overlaps = std::mem::take(&mut self.overlaps);
+        collisions.clear();
+        overlaps.clear();
+        // Contacts between bodies that didn't move still hold.
+        let still = |&(a, b): &(BodyId, BodyId)| {
+            let still = |id| self.get_body(id).is_some_and(|body| !body.moving());
+            still(a) && still(b)
+        };
+        collisions.extend(self.previous_collisions.iter().filter(|pair| still(pair)));
+        overlaps.extend(self.previous_overlaps.iter().filter(|pair| still(pair)));
+        // Other pairs are found from the moving bodies, as there are
+        // usually far fewer of them than of fixed or sleeping ones. Each
+        // pair is ordered as the bodies are in `bodies`.
+        self.bodies()
+            .filter(|(_, body)| body.moving())
+            .flat_map(|moving| {
+                self.bodies()
+                    .filter(move |(id, body)| !body.moving() || id.index > moving.0.index)
+                    .map(move |other| {
+                        if other.0.index < moving.0.index {
+                            (other, moving)
+                        } else {
+                            (moving, other)
+                        }
+                    })
+            })
+            .filter(|((_, body1), (_, body2))| {
+                body1.interacts_with(body2) && collision::bodies_overlap(body1, body2)
+            })
+            .for_each(|((id1, body1), (id2, body2))| {
+                if body1.sensor || body2.sensor {
+                    overlaps.push((id1, id2));
+                } else {
+                    collisions.push((id1, id2));
+                }
+            });
+        // Sorted pairs are looked up quickly when telling new contacts.
+        collisions.sort_unstable_by_key(|(a, b)| (a.index, b.index));
+        overlaps.sort_unstable_by_key(|(a, b)| (a.index, b.index));
+
+        let mut events = std::mem::take(&mut self.events);
+        events.clear();
+        events.append(&mut self.removed_contacts);
+        self.push_events(&self.previous_collisions, &collisions, false, &mut events);
+        self.push_events(&self.previous_overlaps, &overlaps, true, &mut events);
+        self.events = events;
+        self.collisions = collisions;
+        self.overlaps = overlaps;
+        self.update_sleep(dt);
+    }
+
+    /// Pushes the events telling `previous` contacts from `current` ones,
+    /// both sorted by the slot indices of their bodies.
+    fn push_events(
+        &self,
+        previous: &[(BodyId, BodyId)],
+        current: &[(BodyId, BodyId)],
+        sensor: bool,
+        events: &mut Vec<ContactEvent>,
+    ) {
+        let contains = |pairs: &[(BodyId, BodyId)], pair: &(BodyId, BodyId)| {
+            pairs
+                .binary_search_by_key(&(pair.0.index, pair.1.index), |(a, b)| (a.index, b.index))
+                .is_ok_and(|i| pairs[i] == *pair)
+        };
+        let began_or_persisted = current.iter().map(|pair| {
+            if contains(previous, pair) {
+                (pair, ContactPhase::Persist)
+            } else {
+                (pair, ContactPhase::Begin)
+            }
+        });
+        let ended = previous
+            .iter()
+            .filter(|pair| !contains(current, pair))
+            .map(|pair| (pair, ContactPhase::End));
+        events.extend(
+            began_or_persisted
+                .chain(ended)
+                .filter_map(|(&bodies, phase)| {
+                    let body1 = self.get_body(bodies.0)?;
+                    let body2 = self.get_body(bodies.1)?;
+                    Some(contact_event(bodies, (body1, body2), phase, sensor))
+                }),
+        );
+    }
+
+    pub fn add_body(&mut self, b: ConvexBody) -> BodyId {
+        // Sleeping bodies don't look for new contacts, so the ones the new
+        // body lands on are woken up to find it.
+        self.slots
+            .iter_mut()
+            .filter_map(|slot| slot.body.as_mut())
+            .filter(|body| {
+                body.sleeping && body.interacts_with(&b) && collision::bodies_overlap(body, &b)
+            })
+            .for_each(|body| body.wake());
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.body = Some(b);
+                BodyId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    body: Some(b),
+                });
+                BodyId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Removes the body behind `id` and returns it. Returns `None` if it
+    /// was already removed.
+    pub fn remove_body(&mut self, id: BodyId) -> Option<ConvexBody> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        let body = slot.body.take()?;
+        slot.generation += 1;
+        self.free.push(id.index);
+        // Its contacts end, and bodies resting on it may have to move again.
+        let contacts = self.collisions.iter().map(|pair| (pair, false));
+        let overlaps = self.overlaps.iter().map(|pair| (pair, true));
+        for (&(a, b), sensor) in contacts.chain(overlaps) {
+            let other = match (a, b) {
+                (a, other) if a == id => other,
+                (other, b) if b == id => other,
+                _ => continue,
+            };
+            if let Some(other) = self.slots[other.index as usize].body.as_mut() {
+                other.wake();
+                let pair = if a == id {
+                    (&body, &*other)
+                } else {
+                    (&*other, &body)
+                };
+                self.removed_contacts
+                    .push(contact_event((a, b), pair, ContactPhase::End, sensor));
+            }
+        }
+        self.collisions.retain(|&(a, b)| a != id && b != id);
+        self.overlaps.retain(|&(a, b)| a != id && b != id);
+        self.events.retain(|e| e.other(id).is_none());
+        self.joints.retain(|j| j.bodies.0 != id && j.bodies.1 != id);
+        Some(body)
+    }
+
+    pub fn get_body(&self, id: BodyId) -> Option<&ConvexBody> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.body.as_ref())
+    }
+
+    pub fn get_body_mut(&mut self, id: BodyId) -> Option<&mut ConvexBody> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.body.as_mut())
+    }
+
+    /// All bodies with their handles, in the order they were added to
+    /// their slots.
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyId, &ConvexBody)> + Clone {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let id = BodyId {
+                index: index as u32,
+                generation: slot.generation,
+            };
+            slot.body.as_ref().map(|body| (id, body))
+        })
+    }
+
+    pub fn bodies_mut(&mut self) -> impl Iterator<Item = (BodyId, &mut ConvexBody)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let id = BodyId {
+                    index: index as u32,
+                    generation: slot.generation,
+                };
+                slot.body.as_mut().map(|body| (id, body))
+            })
+    }
+}
+
+/// Projects `mesh` onto `axis`, returning the interval it covers
+/// in units of the axis length.
+fn project(mesh: &[Position], axis: &Vector) -> (f64, f64) {
+    mesh.iter()
+        .map(|p| p.dot(*axis))
+        .fold((f64::MAX, f64::MIN), |(min, max), d| {
+            (min.min(d), max.max(d))
+        })
+}
+
+/// Mean of the vertices of `mesh`.
+pub fn centroid(mesh: &[Position]) -> Position {
+    mesh.iter().copied().sum::<Position>() / mesh.len() as f64
+}
+
+/// Returns the bottom left and top right corners of the
+/// bounding box of `mesh`.
+pub fn bounds(mesh: &[Position]) -> (Position, Position) {
+    mesh.iter().fold(
+        (pos(f64::MAX, f64::MAX), pos(f64::MIN, f64::MIN)),
+        |(min, max), p| {
+            (
+                pos(min.x.min(p.x), min.y.min(p.y)),
+                pos(max.x.max(p.x), max.y.max(p.y)),
+            )
+        },
+    )
+}
+
+/// Checks for collision between two convex polygons using
+/// the "separating axis theorem" approach.
+pub fn collided(shape1: &[Position], shape2: &[Position]) -> bool {
+    !(check_for_separating_axis(shape1, shape2) || check_for_separating_axis(shape2, shape1))
+}
+
+/// Checks for a separating axis between `shape1` and `shape2`. It does that
+/// based on the shape projections onto lines that are solely perpendicular to
+/// the edges of `shape1`.
+fn check_for_separating_axis(shape1: &[Position], shape2: &[Position]) -> bool {
+    shape1.iter().circular_tuple_windows().any(|(p1, p2)| {
+        let normal = (*p2 - *p1).perpendicular();
+        let (shape1_min, shape1_max) = project(shape1, &normal);
+        let (shape2_min, shape2_max) = project(shape2, &normal);
+        shape1_max < shape2_min || shape2_max < shape1_min
+    })
+}
+
+pub fn generate_terrain() -> Vec<Position> {
+    generate_terrain_with(&mut rand::thread_rng())
+}
+
+/// Generates terrain out of `rng`, so the same seed always
+/// gives the same terrain.
+pub fn generate_terrain_with<R: Rng>(rng: &mut R) -> Vec<Position> {
+    let left_limit = 0.0;
+    let right_limit = 100.0;
+
+    let mut x = left_limit;
+    let mut terrain = vec![];
+
+    for _ in 0..21 {
+        terrain.push(pos(x, rng.gen_range(2.0..20.0)));
+        x += right_limit / 20.0;
+    }
+
+    let landing_site_index = rng.gen_range(0..terrain.len() - 1);
+
+    let landing_site_height = terrain.get(landing_site_index).unwrap().y;
+    terrain.get_mut(landing_site_index + 1).unwrap().y = landing_site_height;
+
+    terrain
+}
+
+#[derive(PartialEq, Debug)]
+pub struct TerrainPartition {
+    pub safe: bool,
+    pub mesh: [Position; 4],
+}
+
+/// How far below the lowest point of the terrain its partitions go,
+/// in meters.
+pub const TERRAIN_DEPTH: f64 = 10.0;
+
+/// Partitions `terrain` onto non-convex polygons so they can
+/// be used later in collision detection, plus, tags the partition
+/// with a safe or non-safe (for landing) attribute.
+///
+/// Partitions all go down to `TERRAIN_DEPTH` below the lowest point of
+/// `terrain`, so the ones under peaks are as thick as the peaks are high.
+pub fn partition_terrain(terrain: &[Position]) -> Vec<TerrainPartition> {
+    partition_terrain_to(terrain, terrain_floor(terrain))
+}
+
+/// Floor `partition_terrain` puts the partitions of `terrain` on.
+pub fn terrain_floor(terrain: &[Position]) -> f64 {
+    terrain.iter().map(|p| p.y).fold(f64::INFINITY, f64::min) - TERRAIN_DEPTH
+}
+
+/// Same as `partition_terrain`, with partitions going down to `floor`,
+/// which has to be below every point of `terrain`.
+pub fn partition_terrain_to(terrain: &[Position], floor: f64) -> Vec<TerrainPartition> {
+    debug_assert!(
+        terrain.iter().all(|p| p.y > floor),
+        "floor {floor} isn't below the terrain"
+    );
+    terrain
+        .iter()
+        .tuple_windows()
+        .map(|(p1, p2)| TerrainPartition {
+            safe: p1.y == p2.y,
+            mesh: [*p1, *p2, pos(p2.x, floor), pos(p1.x, floor)],
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Landing {
+    Safe,
+    Crash,
+}
+
+/// Maximum speed, on each axis, at which the lander can touch
+/// the ground without crashing.
+pub const MAX_LANDING_SPEED: f64 = 0.55;
+
+/// Thrust of the main engine and of the lateral thrusters, in newtons.
+pub const MAIN_ENGINE_THRUST: f64 = 100.0;
+pub const LATERAL_THRUST: f64 = 100.0;
+
+/// Engine commands, as fractions of the available thrust.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Thrust {
+    /// Main engine throttle, from 0 (off) to 1 (full thrust).
+    pub main: f64,
+    /// Lateral thrusters, from -1 (full thrust towards -x) to 1
+    /// (full thrust towards +x).
+    pub lateral: f64,
+}
+
+/// Evaluates a touchdown on terrain that is, or is not, `safe`
+/// for landing, at `velocity`.
+/// This only looks at the first contact; `gear::Touchdown` follows
+/// the legs until the lander comes to rest.
+pub fn evaluate_landing(safe: bool, velocity: &Vector) -> Landing {
+    if safe && velocity.x.abs() < MAX_LANDING_SPEED && velocity.y.abs() < MAX_LANDING_SPEED {
+        Landing::Safe
+    } else {
+        Landing::Crash
+    }
+}
+
+/// Sets the forces of the main engine and of the lateral thrusters
+/// on the lander.
+pub fn apply_thrust(lander: &mut ConvexBody, thrust: &Thrust) {
+    apply_thrust_along(lander, thrust, &v(0.0, 1.0));
+}
+
+/// Same as `apply_thrust`, for a lander holding the unit vector `up`
+/// as its vertical. Lateral thrust pushes to the right of `up`.
+pub fn apply_thrust_along(lander: &mut ConvexBody, thrust: &Thrust, up: &Vector) {
+    let main = thrust.main.clamp(0.0, 1.0) * MAIN_ENGINE_THRUST;
+    let lateral = thrust.lateral.clamp(-1.0, 1.0) * LATERAL_THRUST;
+    let (main, lateral) = (*up * main, -up.perpendicular() * lateral);
+    lander.set_resulting_force(0.0, 0.0);
+    lander.apply_force(main.x, main.y);
+    lander.apply_force(lateral.x, lateral.y);
+}
+
+pub const MOON_GRAVITY: f64 = 1.625;
+
+/// Creates the lander at its starting point, at the top of the screen.
+pub fn create_lander() -> ConvexBody {
+    ConvexBody::still_body(
+        10.0,
+        &[
+            pos(49.0, 100.0),
+            pos(51.0, 100.0),
+            pos(51.0, 98.0),
+            pos(49.0, 98.0),
+        ],
+    )
+    .with_layers(layer::LANDER, layer::ALL)
+    .with_legs(gear::lander_legs())
+}
+
+/// Adds `partitions` to `engine` as fixed bodies. Returns the id
+/// of each body along with whether it's safe for landing.
+pub fn add_terrain(engine: &mut Engine, partitions: &[TerrainPartition]) -> Vec<(BodyId, bool)> {
+    partitions
+        .iter()
+        .map(|partition| {
+            let partition_id = engine.add_body(ConvexBody::fixed_body(&partition.mesh));
+            (partition_id, partition.safe)
+        })
+        .collect()
+}
+
+/// Returns the body `body_id` collided with in the last tick, if any.
+pub fn touched_body(engine: &Engine, body_id: BodyId) -> Option<BodyId> {
+    engine.collisions.iter().find_map(|&(a, b)| match (a, b) {
+        (id, other) if other == body_id => Some(id),
+        (other, id) if other == body_id => Some(id),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn create_still_body() {
+        let mut engine = Engine::create(10.0);
+        let id = engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
+        engine.tick(1.0);
+        {
+            let body = engine.get_body(id).unwrap();
+            assert_eq!(body.mesh(), [pos(100.0, 95.0)]);
+        }
+        engine.tick(1.0);
+        {
+            let body = engine.get_body(id).unwrap();
+            assert_eq!(body.mesh(), [pos(100.0, 80.0)]);
+        }
+    }
+
+    #[test]
+    fn create_body_from_mesh() {
+        let clockwise = positions![(0.0, 0.0), (0.0, 2.0), (2.0, 2.0), (2.0, 0.0)];
+        let body = ConvexBody::from_mesh(1.0, &clockwise).unwrap();
+        assert_eq!(body.position(), pos(1.0, 1.0));
+        assert!(shape::signed_area(body.mesh()) > 0.0);
+        let bow_tie = positions![(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)];
+        assert_eq!(
+            ConvexBody::from_mesh(1.0, &bow_tie).err(),
+            Some(shape::ShapeError::SelfIntersecting)
+        );
+    }
+
+    #[test]
+    fn free_fall_on_moon() {
+        let mut engine = Engine::create(1.625);
+        let id = engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
+        engine.tick(1.0);
+        {
+            let body = engine.get_body(id).unwrap();
+            assert_eq!(body.mesh(), [pos(100.0, 99.1875)]);
+        }
+    }
+
+    #[test]
+    fn force_opposite_to_gravity() {
+        let mut engine = Engine::create(10.0);
+        let id = engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
+        engine.get_body_mut(id).unwrap().apply_force(0.0, 100.0);
+
+        engine.tick(1.0);
+
+        let body = engine.get_body(id).unwrap();
+
+        assert_eq!(body.mesh(), [pos(100.0, 100.0)]);
+    }
+
+    #[test]
+    fn set_resulting_force() {
+        let mut engine = Engine::create(10.0);
+        let id = engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
+        engine
+            .get_body_mut(id)
+            .unwrap()
+            .set_resulting_force(0.0, 100.0);
+
+        engine.tick(1.0);
+
+        let body = engine.get_body(id).unwrap();
+
+        assert_eq!(body.mesh(), [pos(100.0, 100.0)]);
+    }
+
+    #[test]
+    fn forces_on_both_axis() {
+        let mut engine = Engine::create(0.0);
+        let id = engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
+
+        {
+            let body = engine.get_body_mut(id).unwrap();
+
+            body.apply_force(100.0, 0.0);
+            body.apply_force(0.0, 100.0);
+        }
+
+        engine.tick(1.0);
+
+        let body = engine.get_body(id).unwrap();
+        assert_eq!(body.mesh(), [pos(105.0, 105.0)]);
+    }
+
+    #[test]
+    fn bodies_share_shapes() {
+        let shape: Arc<[Position]> =
+            Arc::from(positions![(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].as_slice());
+        let mut engine = Engine::create(0.0);
+        let spinning = engine.add_body(ConvexBody::from_shape(
+            1.0,
+            shape.clone(),
+            Transform2::IDENTITY,
+        ));
+        let still = engine.add_body(ConvexBody::from_shape(
+            1.0,
+            shape.clone(),
+            Transform2::new(pos(10.0, 0.0), 0.0),
+        ));
+        let body = engine.get_body_mut(spinning).unwrap();
+        body.velocity = v(0.3, 0.1);
+        body.angular_velocity = 1.0;
+        (0..600).for_each(|_| engine.tick(1.0 / 60.0));
+
+        assert_eq!(Arc::strong_count(&shape), 3);
+        let body = engine.get_body(spinning).unwrap();
+        assert!((body.angle() - 10.0).abs() < 1e-9);
+        assert_eq!(body.mesh()[2], body.transform().apply(shape[2]));
+        assert_eq!(
+            engine.get_body(still).unwrap().mesh(),
+            positions![(9.0, -1.0), (11.0, -1.0), (11.0, 1.0), (9.0, 1.0)]
+        );
+        assert_eq!(create_lander().position(), pos(50.0, 99.0));
+    }
+
+    #[test]
+    fn boulders_and_pods_hit_the_ground() {
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let ground = engine.add_body(ConvexBody::fixed_body(&positions![
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, -1.0),
+            (0.0, -1.0)
+        ]));
+        let boulder = engine.add_body(ConvexBody::circle(50.0, pos(2.0, 1.0), 0.8));
+        let pod = engine.add_body(ConvexBody::capsule(20.0, pos(5.0, 1.0), pos(7.0, 1.0), 0.5));
+        engine.tick(0.1);
+        assert!(!engine.has_collisions());
+
+        (0..10).for_each(|_| engine.tick(0.1));
+        assert_eq!(engine.collisions, [(ground, boulder), (ground, pod)]);
+        let boulder = engine.get_body(boulder).unwrap();
+        assert_eq!(boulder.position().x, 2.0);
+        assert!((gear::moment_of_inertia(boulder) - 16.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compound_bodies_tell_parts_apart() {
+        let mut engine = Engine::create(10.0);
+        let ground = engine.add_body(ConvexBody::fixed_body(&positions![
+            (0.0, 0.5),
+            (10.0, 0.5),
+            (10.0, -1.0),
+            (0.0, -1.0)
+        ]));
+        let leg: Arc<[Position]> = Arc::from(positions![(0.0, 0.0), (0.0, -1.0)].as_slice());
+        let lander = ConvexBody::still_body(
+            10.0,
+            &positions![(4.0, 2.0), (6.0, 2.0), (6.0, 4.0), (4.0, 4.0)],
+        )
+        .with_part(Part::create(
+            leg.clone(),
+            0.0,
+            Transform2::new(v(-1.0, -1.0), 0.0),
+        ))
+        .with_part(Part::create(leg, 0.0, Transform2::new(v(1.0, -1.0), 0.0)));
+        assert_eq!(
+            lander.primitives().nth(2).unwrap().core(),
+            positions![(6.0, 2.0), (6.0, 1.0)]
+        );
+        let lander = engine.add_body(lander);
+
+        let event = loop {
+            engine.tick(0.05);
+            if let Some(event) = engine.contact_events().first() {
+                break event.clone();
+            }
+        };
+        assert_eq!(event.bodies, (ground, lander));
+        // Both legs touched, the cabin didn't.
+        assert_eq!(event.parts, Some((0b1, 0b110)));
+
+        let lander = engine.get_body_mut(lander).unwrap();
+        lander.rotate(std::f64::consts::PI);
+        let legs: Vec<f64> = lander.primitives().skip(1).map(|p| p.core()[1].y).collect();
+        assert!(legs
+            .iter()
+            .all(|y| (y - lander.position().y - 2.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn moving_bodies_refill_their_meshes() {
+        let leg: Arc<[Position]> = Arc::from(positions![(0.0, 0.0), (0.0, -1.0)].as_slice());
+        let mut body = ConvexBody::still_body(1.0, &positions![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)])
+            .with_part(Part::create(leg, 0.0, Transform2::new(v(0.0, 0.0), 0.0)));
+        let mesh = body.mesh().as_ptr();
+        let part = body.parts[0].mesh(&body.transform).as_ptr();
+
+        body.translate(v(2.0, 0.0));
+        assert_eq!(body.mesh()[1], pos(3.0, 0.0));
+        assert_eq!(body.mesh().as_ptr(), mesh);
+        body.rotate(1.0);
+        assert_eq!(body.parts[0].mesh(&body.transform).as_ptr(), part);
+    }
+
+    #[test]
+    fn mesh_projection() {
+        let square = positions![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        assert_eq!(project(&square, &v(1.0, 0.0)), (0.0, 2.0));
+        assert_eq!(project(&square, &v(0.0, -1.0)), (-2.0, 0.0));
+        assert_eq!(project(&square, &v(1.0, 1.0)), (0.0, 4.0));
+        assert_eq!(project(&square, &v(1.0, -1.0)), (-2.0, 2.0));
+    }
+
+    #[test]
+    fn collision_two_non_intersecting_triangles() {
+        let triangle1 = positions![(1.0, 1.0), (3.0, 1.0), (2.0, 3.0)];
+        let triangle2 = positions![(3.0, 3.0), (4.0, 1.0), (5.0, 3.0)];
+        assert!(!collided(&triangle1, &triangle2));
+    }
+
+    #[test]
+    fn collision_two_triangles_sharing_one_edge() {
+        let triangle1 = positions![(1.0, 1.0), (3.0, 1.0), (2.0, 3.0)];
+        let triangle2 = positions![(2.0, 3.0), (3.0, 1.0), (4.0, 3.0)];
+        assert!(collided(&triangle1, &triangle2));
+    }
+
+    #[test]
+    fn collision_two_triangles_overlapping() {
+        let triangle1 = positions![(1.0, 1.0), (3.0, 1.0), (2.0, 3.0)];
+        let triangle2 = positions![(2.0, 2.0), (1.0, 4.0), (3.0, 4.0)];
+        assert!(collided(&triangle1, &triangle2));
+    }
+
+    #[test]
+    fn collision_two_rectangles() {
+        let mesh1 = [
+            pos(0.0, 20.0),
+            pos(100.0, 20.0),
+            pos(100.0, 10.0),
+            pos(0.0, 10.0),
+        ];
+
+        let mesh2 = [
+            pos(40.0, 20.0),
+            pos(50.0, 20.0),
+            pos(50.0, 30.0),
+            pos(40.0, 30.0),
+        ];
+
+        assert!(collided(&mesh1, &mesh2));
+    }
+
+    #[test]
+    fn collision_trapezoid_and_rectangle() {
+        let trapezoid = [
+            pos(0.0, 0.0),
+            pos(20.0, 0.0),
+            pos(10.0, 10.0),
+            pos(0.0, 10.0),
+        ];
+
+        let rectangle = [
+            pos(16.0, 5.0),
+            pos(25.0, 5.0),
+            pos(25.0, 15.0),
+            pos(16.0, 15.0),
+        ];
+
+        assert!(!collided(&rectangle, &trapezoid));
+        assert!(!collided(&rectangle, &trapezoid));
+    }
+
+    #[test]
+    fn collision_bug_test() {
+        let m1 = [
+            crate::Position {
+                x: 49.0,
+                y: 15.743524305555098,
+            },
+            crate::Position {
+                x: 51.0,
+                y: 15.743524305555098,
+            },
+            crate::Position {
+                x: 51.0,
+                y: 13.743524305555086,
+            },
+            crate::Position {
+                x: 49.0,
+                y: 13.743524305555086,
+            },
+        ];
+
+        let m2 = [
+            crate::Position {
+                x: 50.0,
+                y: 13.619878363341645,
+            },
+            crate::Position {
+                x: 55.0,
+                y: 14.721771340827875,
+            },
+            crate::Position {
+                x: 55.0,
+                y: 4.721771340827875,
+            },
+            crate::Position {
+                x: 50.0,
+                y: 3.6198783633416447,
+            },
+        ];
+
+        assert!(collided(&m1, &m2));
+    }
+
+    #[test]
+    fn partition_terrain_test() {
+        let terrain = positions![(0.0, 5.0), (1.0, 6.0), (2.0, 4.0), (3.0, 4.0)];
+        let polygons = partition_terrain(&terrain);
+        assert_eq!(
+            polygons.as_slice(),
+            &[
+                TerrainPartition {
+                    safe: false,
+                    mesh: positions![(0.0, 5.0), (1.0, 6.0), (1.0, -6.0), (0.0, -6.0)]
+                },
+                TerrainPartition {
+                    safe: false,
+                    mesh: positions![(1.0, 6.0), (2.0, 4.0), (2.0, -6.0), (1.0, -6.0)]
+                },
+                TerrainPartition {
+                    safe: true,
+                    mesh: positions![(2.0, 4.0), (3.0, 4.0), (3.0, -6.0), (2.0, -6.0)]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn partition_terrain_to_a_common_floor() {
+        // A deep valley between tall peaks.
+        let terrain = positions![(0.0, 2.0), (5.0, 180.0), (10.0, 180.0), (15.0, -40.0)];
+        let partitions = partition_terrain(&terrain);
+        assert!(partitions
+            .iter()
+            .all(|p| p.mesh[2].y == -50.0 && p.mesh[3].y == -50.0));
+        assert!(partitions.iter().all(|p| shape::validate(&p.mesh).is_ok()));
+        // Under the peaks, where partitions 10 m thick left a hole.
+        let square = positions![(7.0, 60.0), (8.0, 60.0), (8.0, 59.0), (7.0, 59.0)];
+        assert!(collided(&square, &partitions[1].mesh));
+
+        let partitions = partition_terrain_to(&terrain, -100.0);
+        assert!(partitions.iter().all(|p| p.mesh[2].y == -100.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn partition_terrain_to_a_floor_above_the_terrain() {
+        partition_terrain_to(&positions![(0.0, 0.0), (1.0, 5.0)], 2.0);
+    }
+
+    #[test]
+    fn landing_evaluation() {
+        assert_eq!(evaluate_landing(true, &v(0.1, -0.5)), Landing::Safe);
+        assert_eq!(evaluate_landing(false, &v(0.1, -0.5)), Landing::Crash);
+        assert_eq!(evaluate_landing(true, &v(0.6, -0.1)), Landing::Crash);
+        assert_eq!(evaluate_landing(true, &v(0.0, -2.0)), Landing::Crash);
+    }
+
+    #[test]
+    fn collision_between_non_fixed_and_fixed_body() {
+        let mut engine = Engine::create(10.0);
+        engine.add_body(ConvexBody::fixed_body(&positions![
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, -1.0),
+            (0.0, -1.0)
+        ]));
+        engine.add_body(ConvexBody::still_body(
+            10.0,
+            &positions![(1.0, 1.0), (2.0, 1.0), (1.5, 2.0)],
+        ));
+        engine.tick(0.5);
+    }
+
+    #[test]
+    fn removed_handles_stay_invalid() {
+        let mut engine = Engine::create(0.0);
+        let first = engine.add_body(ConvexBody::still_body(1.0, &[pos(0.0, 0.0)]));
+        let second = engine.add_body(ConvexBody::still_body(2.0, &[pos(1.0, 0.0)]));
+
+        assert_eq!(engine.remove_body(first).unwrap().mass, 1.0);
+        assert!(engine.get_body(first).is_none());
+        assert!(engine.remove_body(first).is_none());
+
+        let third = engine.add_body(ConvexBody::still_body(3.0, &[pos(2.0, 0.0)]));
+        assert_ne!(third, first);
+        assert!(engine.get_body(first).is_none());
+        assert_eq!(engine.get_body(third).unwrap().mass, 3.0);
+        assert_eq!(engine.get_body(second).unwrap().mass, 2.0);
+        assert_eq!(
+            engine.bodies().map(|(id, _)| id).collect::<Vec<_>>(),
+            [third, second]
+        );
+    }
+
+    #[test]
+    fn collisions_report_handles() {
+        let square = positions![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mut engine = Engine::create(0.0);
+        let debris = engine.add_body(ConvexBody::still_body(1.0, &square));
+        let ground = engine.add_body(ConvexBody::fixed_body(&square));
+        let lander = engine.add_body(ConvexBody::still_body(1.0, &square));
+        engine.remove_body(debris);
+        engine.tick(0.1);
+
+        assert_eq!(engine.collisions, [(ground, lander)]);
+        assert_eq!(touched_body(&engine, lander), Some(ground));
+
+        engine.remove_body(ground);
+        assert!(!engine.has_collisions());
+    }
+
+    #[test]
+    fn layers_filter_pairs() {
+        let square = positions![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mut engine = Engine::create(0.0);
+        engine.add_body(ConvexBody::fixed_body(&square));
+        engine.add_body(ConvexBody::fixed_body(&square));
+        let lander = engine
+            .add_body(ConvexBody::still_body(1.0, &square).with_layers(layer::LANDER, layer::ALL));
+        engine.add_body(
+            ConvexBody::still_body(1.0, &square)
+                .with_layers(layer::DEBRIS, layer::TERRAIN | layer::DEBRIS),
+        );
+        engine.tick(0.1);
+
+        // Terrain against terrain and debris against the lander are skipped.
+        assert_eq!(engine.collisions.len(), 4);
+        assert_eq!(
+            engine
+                .collisions
+                .iter()
+                .filter(|&&(a, b)| a == lander || b == lander)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn sensors_report_overlaps() {
+        let square = positions![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mut engine = Engine::create(0.0);
+        let zone = engine.add_body(
+            ConvexBody::fixed_body(&square)
+                .with_layers(layer::SENSOR, layer::LANDER)
+                .sensor(),
+        );
+        let lander = engine.add_body(create_lander());
+        engine.tick(0.1);
+        assert!(engine.overlaps.is_empty());
+
+        let body = engine.get_body_mut(lander).unwrap();
+        body.translate(centroid(&square) - body.position());
+        engine.tick(0.1);
+        assert_eq!(engine.overlaps, [(zone, lander)]);
+        assert!(!engine.has_collisions());
+    }
+
+    #[test]
+    fn contact_phases() {
+        let mut engine = Engine::create(0.0);
+        let ground = engine.add_body(ConvexBody::fixed_body(&positions![
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, -1.0),
+            (0.0, -1.0)
+        ]));
+        let lander = engine.add_body(ConvexBody::still_body(
+            10.0,
+            &positions![(1.0, 1.5), (2.0, 1.5), (2.0, 0.5), (1.0, 0.5)],
+        ));
+        engine.get_body_mut(lander).unwrap().velocity = v(0.0, -1.0);
+        let phases = |engine: &Engine| {
+            engine
+                .contact_events()
+                .iter()
+                .map(|e| e.phase)
+                .collect::<Vec<_>>()
+        };
+
+        engine.tick(0.25);
+        assert!(engine.contact_events().is_empty());
+        engine.tick(0.25);
+        assert_eq!(
+            engine.contact_events(),
+            [ContactEvent {
+                bodies: (ground, lander),
+                phase: ContactPhase::Begin,
+                sensor: false,
+                relative_velocity: v(0.0, -1.0),
+                impulse: 10.0,
+                parts: Some((1, 1)),
+            }]
+        );
+        engine.tick(0.25);
+        assert_eq!(phases(&engine), [ContactPhase::Persist]);
+
+        engine.get_body_mut(lander).unwrap().velocity = v(0.0, 10.0);
+        engine.tick(0.25);
+        assert_eq!(phases(&engine), [ContactPhase::End]);
+        assert_eq!(engine.contact_events()[0].parts, None);
+        engine.tick(0.25);
+        assert!(engine.contact_events().is_empty());
+    }
+
+    #[test]
+    fn removing_a_body_ends_its_contacts() {
+        let mut engine = Engine::create(0.0);
+        let ground = engine.add_body(ConvexBody::fixed_body(&positions![
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, -1.0),
+            (0.0, -1.0)
+        ]));
+        let zone = engine.add_body(ConvexBody {
+            sensor: true,
+            ..ConvexBody::fixed_body(&positions![(0.0, 3.0), (4.0, 3.0), (4.0, 0.0), (0.0, 0.0)])
+        });
+        let lander = engine.add_body(ConvexBody::still_body(
+            10.0,
+            &positions![(1.0, 0.5), (2.0, 0.5), (2.0, -0.5), (1.0, -0.5)],
+        ));
+        engine.tick(0.25);
+        assert_eq!(engine.contact_events().len(), 2);
+
+        engine.remove_body(ground);
+        engine.remove_body(zone);
+        engine.tick(0.25);
+        let ended = |bodies, sensor| ContactEvent {
+            bodies,
+            phase: ContactPhase::End,
+            sensor,
+            relative_velocity: v(0.0, 0.0),
+            impulse: 0.0,
+            parts: None,
+        };
+        assert_eq!(
+            engine.contact_events(),
+            [ended((ground, lander), false), ended((zone, lander), true)]
+        );
+        engine.tick(0.25);
+        assert!(engine.contact_events().is_empty());
+    }
+
+    #[test]
+    fn falls_at_terminal_velocity_in_atmosphere() {
+        let mut engine = Engine::create(1.352);
+        engine.atmosphere = Some(atmosphere::Atmosphere::titan());
+        let id = engine.add_body(ConvexBody::still_body(
+            10.0,
+            &positions![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)],
+        ));
+        (0..600).for_each(|_| engine.tick(0.05));
+
+        let terminal = (2.0 * 10.0 * 1.352_f64 / (5.3 * 2.0)).sqrt();
+        let body = engine.get_body(id).unwrap();
+        assert!((body.velocity.y + terminal).abs() < 0.01);
+        assert!((engine.time() - 30.0).abs() < 1e-9);
+    }
+}
@@ -1,124 +1,48 @@
+use fma::autopilot::{self, Autopilot};
+use fma::crater::CRASH_CRATER_RADIUS;
+use fma::debris::DEBRIS_PIECES;
+use fma::gear::Touchdown;
+use fma::orbit::{self, local_up, to_local, Mission};
+use fma::{
+    add_terrain, apply_thrust_along, create_lander, generate_terrain, partition_terrain, pos,
+    terrain_floor, v, BodyId, ContactPhase, ConvexBody, Engine, Landing, Position, Thrust, Vector,
+    LATERAL_THRUST, MAIN_ENGINE_THRUST, MOON_GRAVITY,
+};
 use itertools::Itertools;
 use piston_window::*;
-use rand::Rng;
-use std::fmt;
 
-#[derive(Debug)]
-struct Vector {
-    x: f64,
-    y: f64,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct Position {
-    x: f64,
-    y: f64,
-}
-
-impl fmt::Display for Position {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({},{})", self.x, self.y)
-    }
-}
-
-fn v(x: f64, y: f64) -> Vector {
-    Vector { x, y }
-}
-
-fn pos(x: f64, y: f64) -> Position {
-    Position { x, y }
-}
+mod input;
+mod particles;
+mod render;
 
-#[allow(unused_macros)]
-macro_rules! positions {
-    ($(($x:expr, $y:expr)),*) => {
-        [$(crate::Position{ x: $x, y: $y }),*]
-    }
-}
-
-#[derive(Debug)]
-struct ConvexBody {
-    mass: f64,
-    mesh: Vec<Position>,
-    acceleration: Vector,
-    velocity: Vector,
-    fixed: bool,
-    report_collision: bool,
-}
-
-impl ConvexBody {
-    fn still_body(m: f64, mesh: &[Position]) -> ConvexBody {
-        ConvexBody {
-            mass: m,
-            mesh: Vec::from(mesh),
-            acceleration: v(0.0, 0.0),
-            velocity: v(0.0, 0.0),
-            fixed: false,
-            report_collision: true,
-        }
-    }
-
-    fn fixed_body(mesh: &[Position]) -> ConvexBody {
-        ConvexBody {
-            mass: 0.0,
-            mesh: Vec::from(mesh),
-            acceleration: v(0.0, 0.0),
-            velocity: v(0.0, 0.0),
-            fixed: true,
-            report_collision: false,
-        }
-    }
-
-    fn apply_force(&mut self, fx: f64, fy: f64) {
-        self.acceleration.x += fx / self.mass;
-        self.acceleration.y += fy / self.mass;
-    }
-
-    fn set_resulting_force(&mut self, fx: f64, fy: f64) {
-        self.acceleration.x = fx / self.mass;
-        self.acceleration.y = fy / self.mass;
-    }
-
-    fn report_collision(mut self) -> Self {
-        self.report_collision = true;
-        self
-    }
-}
-
-struct Engine {
-    bodies: Vec<ConvexBody>,
-    ga: f64,
-    collisions: Vec<(usize, usize)>,
-}
-
-impl Engine {
-    fn create(g: f64) -> Engine {
-        Engine {
-            bodies: vec![],
-            ga: g,
-            collisions: vec![],
-        }
-    }
-
-    fn has_collisions(&self) -> bool {
-        !self.collisions.is_empty()
-    }
-}
+use input::{Action, InputMap};
+use particles::{Emitter, ParticleKind, ParticleSystem};
+use render::LanderModel;
 
+#[derive(Clone)]
 struct ViewPort {
     /// Origin of viewport. It's the top left corner of
     /// the view port in meters.
     origin: Position,
     /// Ratio meter/pixel.
     ratio: f64,
+    /// Counterclockwise angle of the view port up direction
+    /// from the world y axis.
+    rotation: f64,
 }
 
 impl ViewPort {
     fn translate_pos(&self, real_pos: &Position) -> Position {
-        Position {
-            x: (real_pos.x - self.origin.x) / self.ratio,
-            y: (self.origin.y - real_pos.y) / self.ratio,
-        }
+        let d = (*real_pos - self.origin).rotate(-self.rotation);
+        pos(d.x, -d.y) / self.ratio
+    }
+
+    /// Moves the view port so that it shows `size` meters around `center`,
+    /// in `pixels` pixels, with `up` pointing up.
+    fn follow(&mut self, center: &Position, up: &Vector, size: f64, pixels: f64) {
+        self.rotation = up.y.atan2(up.x) - std::f64::consts::FRAC_PI_2;
+        self.ratio = size / pixels;
+        self.origin = *center + v(-size / 2.0, size / 2.0).rotate(self.rotation);
     }
 
     fn translate_size(&self, size: f64) -> f64 {
@@ -126,265 +50,250 @@ impl ViewPort {
     }
 }
 
-type BodyId = usize;
-
-impl Engine {
-    fn update_body_position(body: &mut ConvexBody, ga: f64, dt: f64) -> bool {
-        if body.fixed {
-            return false;
-        }
-        let ax = body.acceleration.x;
-        let ay = body.acceleration.y - ga;
-        let vx = body.velocity.x + (ax * dt);
-        let vy = body.velocity.y + (ay * dt);
-        let sx = (dt / 2.0) * (vx + body.velocity.x);
-        let sy = (dt / 2.0) * (vy + body.velocity.y);
-        body.velocity = v(vx, vy);
-        body.mesh.iter_mut().for_each(|pos| {
-            pos.x += sx;
-            pos.y += sy;
-        });
-        sx != 0.0 || sy != 0.0
-    }
-
-    fn tick(&mut self, dt: f64) {
-        self.bodies.iter_mut().for_each(|body| {
-            Self::update_body_position(body, self.ga, dt);
-        });
-        let collisions = self
-            .bodies
-            .iter()
-            .enumerate()
-            .combinations(2)
-            .filter(|pair| {
-                let body1 = pair[0].1;
-                let body2 = pair[1].1;
-                (body1.report_collision || body2.report_collision)
-                    && collided(body1.mesh.as_slice(), body2.mesh.as_slice())
-            })
-            .map(|v| (v[0].0, v[1].0))
-            .collect::<Vec<_>>();
-        self.collisions = collisions;
-    }
-
-    fn add_body(&mut self, b: ConvexBody) -> BodyId {
-        self.bodies.push(b);
-        self.bodies.len() - 1
-    }
-
-    fn get_bodies(&self) -> &[ConvexBody] {
-        self.bodies.as_slice()
-    }
+/// How much the main engine throttle changes on each
+/// throttle up or down.
+const THROTTLE_STEP: f64 = 0.1;
 
-    fn get_bodies_mut(&mut self) -> &mut [ConvexBody] {
-        self.bodies.as_mut_slice()
+/// Thrust commanded by the pilot through the actions currently held.
+fn pilot_thrust(input_map: &InputMap, throttle: f64) -> Thrust {
+    let held = |action| if input_map.is_held(action) { 1.0 } else { 0.0 };
+    Thrust {
+        main: held(Action::Thrust) * throttle,
+        lateral: held(Action::RotateLeft) - held(Action::RotateRight),
     }
 }
 
-/// Projects point `position` onto a line with gradient
-/// `line_gradient` and y-interception 0.
-fn project(position: &Position, line_gradient: f64) -> Position {
-    let p = position;
-    let a = line_gradient;
-
-    if line_gradient.is_infinite() {
-        pos(0.0, p.y)
-    } else if line_gradient.abs() < f64::EPSILON {
-        pos(p.x, 0.0)
-    } else {
-        let a_orth = -1.0 / a;
-        let b_orth = p.y - (a_orth * p.x);
-        let projected_x = (-b_orth) / (a_orth - a);
-        let projected_y = a * projected_x;
-        pos(projected_x, projected_y)
+/// Reads back the thrust commanded to the lander, holding `up` as its
+/// vertical, from the forces applied to it.
+fn commanded_thrust(lander: &ConvexBody, up: &Vector) -> Thrust {
+    let acceleration = to_local(&lander.acceleration, up);
+    Thrust {
+        main: (acceleration.y * lander.mass / MAIN_ENGINE_THRUST).clamp(0.0, 1.0),
+        lateral: (acceleration.x * lander.mass / LATERAL_THRUST).clamp(-1.0, 1.0),
     }
 }
 
-/// Checks for collision between two convex polygons using
-/// the "separating axis theorem" approach.
-fn collided(shape1: &[Position], shape2: &[Position]) -> bool {
-    !(check_for_separating_axis(shape1, shape2) || check_for_separating_axis(shape2, shape1))
-}
-
-/// Checks for a separating axis between `shape1` and `shape2`. It does that
-/// based on the shape projections onto lines that are solely perpendicular to
-/// the edges of `shape1`.
-fn check_for_separating_axis(shape1: &[Position], shape2: &[Position]) -> bool {
-    !shape1.iter().circular_tuple_windows().all(|(p1, p2)| {
-        let a = (p1.y - p2.y) / (p1.x - p2.x);
-        let a_orth = -1.0 / a;
-
-        let shape1_projections = shape1
-            .iter()
-            .map(|p| project(p, a_orth))
-            .collect::<Vec<_>>();
-
-        let shape2_projections = shape2
-            .iter()
-            .map(|p| project(p, a_orth))
-            .collect::<Vec<_>>();
-
-        let shape1_min = shape1_projections
-            .iter()
-            .fold(pos(f64::MAX, f64::MAX), |min_p, p| {
-                pos(min_p.x.min(p.x), min_p.y.min(p.y))
-            });
-
-        let shape1_max = shape1_projections
-            .iter()
-            .fold(pos(f64::MIN, f64::MIN), |max_p, p| {
-                pos(max_p.x.max(p.x), max_p.y.max(p.y))
-            });
-
-        let shape2_min = shape2_projections
-            .iter()
-            .fold(pos(f64::MAX, f64::MAX), |min_p, p| {
-                pos(min_p.x.min(p.x), min_p.y.min(p.y))
-            });
-
-        let shape2_max = shape2_projections
-            .iter()
-            .fold(pos(f64::MIN, f64::MIN), |max_p, p| {
-                pos(max_p.x.max(p.x), max_p.y.max(p.y))
-            });
-
-        (shape1_max.x >= shape2_min.x && shape2_max.x >= shape1_min.x)
-            && (shape1_max.y >= shape2_min.y && shape2_max.y >= shape1_min.y)
-    })
-}
-
-fn generate_terrain() -> Vec<Position> {
-    let left_limit = 0.0;
-    let right_limit = 100.0;
-
-    let mut rng = rand::thread_rng();
-    let mut x = left_limit;
-    let mut terrain = vec![];
-
-    for _ in 0..21 {
-        terrain.push(pos(x, rng.gen_range(2.0..20.0)));
-        x += right_limit / 20.0;
+/// Meters shown across the window in orbit, at most.
+const ORBIT_VIEW_SIZE: f64 = 600.0;
+
+/// Seconds the lander gets to settle on its legs after touching down.
+const SETTLE_TIMEOUT: f64 = 30.0;
+
+/// Exhaust particles per second at full throttle.
+const EXHAUST_RATE: f64 = 150.0;
+
+/// Lets the autopilot land on a random terrain, without a window,
+/// and prints the outcome once the lander settles on its legs.
+fn headless() {
+    let mut engine = Engine::create(MOON_GRAVITY);
+    let lander_body_id = engine.add_body(create_lander());
+    let partitions = partition_terrain(&generate_terrain());
+    let terrain_safety = add_terrain(&mut engine, &partitions);
+    let mut autopilot = Autopilot::create(&partitions, 50.0).unwrap();
+    println!("landing pad: {:?}", autopilot.pad());
+
+    match autopilot::fly(
+        &mut engine,
+        lander_body_id,
+        &mut autopilot,
+        &terrain_safety,
+        1.0 / 60.0,
+        600.0 + SETTLE_TIMEOUT,
+    ) {
+        Some((time, touchdown)) => {
+            println!(
+                "{:?} after {:.2}s at speed {:?}, leg stroke: {:.2}m, peak leg load: {:.1}N, \
+                 fuel used: {:.2}s of full thrust",
+                touchdown.landing(),
+                time,
+                touchdown.velocity,
+                touchdown.stroke,
+                touchdown.peak_load,
+                autopilot.fuel_used()
+            );
+        }
+        None => println!("no touchdown"),
     }
-
-    let landing_site_index = rng.gen_range(0..terrain.len() - 1);
-
-    let landing_site_height = terrain.get(landing_site_index).unwrap().y;
-    terrain.get_mut(landing_site_index + 1).unwrap().y = landing_site_height;
-
-    terrain
-}
-
-#[derive(PartialEq, Debug)]
-struct TerrainPartition {
-    pub safe: bool,
-    pub mesh: [Position; 4],
-}
-
-/// Partitions `terrain` onto non-convex polygons so they can
-/// be used later in collision detection, plus, tags the partition
-/// with a safe or non-safe (for landing) attribute.
-fn partition_terrain(terrain: &[Position]) -> Vec<TerrainPartition> {
-    terrain
-        .iter()
-        .tuple_windows()
-        .map(|(p1, p2)| TerrainPartition {
-            safe: p1.y == p2.y,
-            mesh: [
-                p1.clone(),
-                p2.clone(),
-                pos(p2.x, p2.y - 10.0),
-                pos(p1.x, p1.y - 10.0),
-            ],
-        })
-        .collect()
 }
 
 fn main() {
-    let viewport = ViewPort {
+    if std::env::args().any(|arg| arg == "--headless") {
+        return headless();
+    }
+    let orbital = std::env::args().any(|arg| arg == "--orbit");
+
+    let mut viewport = ViewPort {
         origin: pos(0.0, 100.0),
         ratio: 0.15,
+        rotation: 0.0,
     };
-
-    let mut window: PistonWindow = WindowSettings::new(
-        "Lander",
-        [
-            viewport.translate_size(100.0),
-            viewport.translate_size(100.0),
-        ],
-    )
-    .exit_on_esc(true)
-    .build()
-    .unwrap();
-
-    let g = 1.625;
-
-    let mut engine = Engine::create(g);
-    let lander = ConvexBody::still_body(
-        10.0,
-        &[
-            pos(49.0, 100.0),
-            pos(51.0, 100.0),
-            pos(51.0, 98.0),
-            pos(49.0, 98.0),
-        ],
-    )
-    .report_collision();
-    let lander_body_id = engine.add_body(lander);
-
-    let terrain = generate_terrain();
-    let terrain_safety: Vec<_> = partition_terrain(terrain.as_slice())
-        .iter()
-        .map(|partition| {
-            let partition_id = engine.add_body(ConvexBody::fixed_body(&partition.mesh));
-            (partition_id, partition.safe)
-        })
-        .collect();
-
+    let window_size = viewport.translate_size(100.0);
+
+    let mut window: PistonWindow = WindowSettings::new("Lander", [window_size, window_size])
+        .exit_on_esc(true)
+        .build()
+        .unwrap();
+
+    let (mut engine, lander_body_id, mut terrain, mut terrain_safety, mut autopilot) = if orbital {
+        let mission = Mission::create_with(&mut rand::thread_rng());
+        // Closes the loop around the moon.
+        let mut terrain = mission.surface.clone();
+        terrain.push(terrain[0]);
+        let safety = mission.terrain_safety;
+        (mission.engine, mission.lander, terrain, safety, None)
+    } else {
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let lander_body_id = engine.add_body(create_lander());
+        let terrain = generate_terrain();
+        let partitions = partition_terrain(terrain.as_slice());
+        let autopilot = Autopilot::create(&partitions, 50.0);
+        let safety = add_terrain(&mut engine, &partitions);
+        (engine, lander_body_id, terrain, safety, autopilot)
+    };
+    // Floor of the partitions of flat terrain, craters can lower it.
+    let mut floor = terrain_floor(&terrain);
+    let mut assist = false;
+    let mut touchdown: Option<Touchdown> = None;
+    let mut landing: Option<Landing> = None;
+    // What's left of the lander after a crash.
+    let mut debris: Vec<BodyId> = vec![];
+
+    let mut input_map = InputMap::load_or_default("controls.toml");
+    let mut throttle = 1.0;
+    let mut paused = false;
+
+    let mut lander_model = LanderModel::new();
+    let mut particles = ParticleSystem::new();
+    let mut exhaust = Emitter::create(ParticleKind::Exhaust, 6.0..10.0, 0.5..1.2);
+    exhaust.direction = -std::f64::consts::FRAC_PI_2;
+    exhaust.spread = 0.15;
+
+    // Where the lander is, or was last seen before it broke up.
+    let mut center = engine.get_body(lander_body_id).unwrap().position();
     while let Some(event) = window.next() {
-        if let Some(update_args) = event.update_args() {
-            engine.tick(update_args.dt);
-            if engine.has_collisions() {
-                let body_id = match engine.collisions.first().unwrap() {
-                    (id, 0) => id,
-                    (0, id) => id,
-                    _ => panic!(),
+        if let Some(body) = engine.get_body(lander_body_id) {
+            center = body.position();
+        }
+        // The lander holds the local vertical, which only changes in orbit.
+        let up = if orbital {
+            local_up(&center)
+        } else {
+            v(0.0, 1.0)
+        };
+        if let Some(update_args) = event.update_args().filter(|_| !paused) {
+            let ga = engine.gravity.strength_at(&center);
+            if let Some(body) = engine
+                .get_body_mut(lander_body_id)
+                .filter(|_| landing.is_none())
+            {
+                if orbital && touchdown.is_none() {
+                    orbit::hold_local_vertical(body);
+                }
+                let thrust = match &mut autopilot {
+                    Some(autopilot) if assist => autopilot.command(body, ga, update_args.dt),
+                    _ => pilot_thrust(&input_map, throttle),
                 };
-                let terrain_safety = terrain_safety.iter().find(|t| t.0 == *body_id).unwrap();
-                let mut body = &mut engine.get_bodies_mut()[0];
-                if terrain_safety.1 && body.velocity.x.abs() < 0.55 && body.velocity.y.abs() < 0.55
-                {
-                    println!("SAFE");
-                } else {
-                    println!(
-                        "terrain is safe: {}, speed: {:?}",
-                        terrain_safety.1, body.velocity
-                    );
-                    println!("YOU'RE DEEEEED!")
+                apply_thrust_along(body, &thrust, &up);
+            }
+            engine.tick(update_args.dt);
+            lander_model.update(update_args.dt);
+            match engine.get_body(lander_body_id) {
+                Some(body) => {
+                    let bottom = body.mesh().iter().map(|p| p.y).fold(f64::MAX, f64::min);
+                    let center = body.position().x;
+                    exhaust.position = pos(center, bottom);
+                    exhaust.velocity = body.velocity;
+                    // Particles only know about flat terrain.
+                    exhaust.rate = if orbital {
+                        0.0
+                    } else {
+                        commanded_thrust(body, &up).main * EXHAUST_RATE
+                    };
+                }
+                None => exhaust.rate = 0.0,
+            }
+            particles.emit(&mut exhaust, update_args.dt);
+            particles.update(update_args.dt, ga, terrain.as_slice());
+            let contact = engine.contact_events().iter().find_map(|e| {
+                e.other(lander_body_id)
+                    .filter(|_| e.phase == ContactPhase::Begin && !e.sensor)
+            });
+            if let (Some(body_id), None) = (contact, &touchdown) {
+                let safe = terrain_safety.iter().any(|t| t.0 == body_id && t.1);
+                let body = engine.get_body(lander_body_id).unwrap();
+                touchdown = Some(Touchdown::begin(safe, &to_local(&body.velocity, &up)));
+            }
+            // The legs take the touchdown until the lander comes to rest,
+            // or until it's clear it won't.
+            if let (Some(touchdown), None) = (&mut touchdown, &landing) {
+                let body = engine.get_body_mut(lander_body_id).unwrap();
+                touchdown.record(body, &up, update_args.dt);
+                landing = touchdown.landing();
+                match landing {
+                    Some(Landing::Safe) => {
+                        println!(
+                            "SAFE, leg stroke: {:.2}m, peak leg load: {:.1}N",
+                            touchdown.stroke, touchdown.peak_load
+                        );
+                        body.set_resulting_force(0.0, 0.0);
+                    }
+                    Some(Landing::Crash) => {
+                        println!(
+                            "terrain is safe: {}, speed: {:?}, leg stroke: {:.2}m, \
+                             peak leg load: {:.1}N, tilt: {:.2}rad",
+                            touchdown.safe,
+                            touchdown.velocity,
+                            touchdown.stroke,
+                            touchdown.peak_load,
+                            touchdown.tilt
+                        );
+                        println!("YOU'RE DEEEEED!");
+                        lander_model.explode(body.mesh());
+                        let impact = body.position();
+                        // Partitions of the whole moon go towards its center,
+                        // not straight down.
+                        if !orbital {
+                            engine.carve_crater(
+                                &mut terrain,
+                                &mut terrain_safety,
+                                &mut floor,
+                                &impact,
+                                CRASH_CRATER_RADIUS,
+                            );
+                        }
+                        debris = engine
+                            .shatter(lander_body_id, DEBRIS_PIECES, &mut rand::thread_rng())
+                            .unwrap_or_default();
+                    }
+                    None => {}
                 }
-                body.set_resulting_force(0.0, 0.0);
-                body.acceleration = v(0.0, 0.0);
-                body.fixed = true;
             }
         }
 
+        // In orbit the camera follows the lander, zooming in as it gets
+        // closer to the ground, and turns with the local vertical.
+        if orbital {
+            let altitude = center.x.hypot(center.y) - orbit::RADIUS;
+            let size = (altitude * 2.5).clamp(100.0, ORBIT_VIEW_SIZE);
+            viewport.follow(&center, &up, size, window_size);
+        }
+
         window.draw_2d(&event, |context, graphics, _device| {
             clear([1.0; 4], graphics);
-            let body = &engine.get_bodies()[0];
-            let p = Polygon::new([1.0, 0.0, 0.0, 1.0]);
-            let polygon_mesh: Vec<[f64; 2]> = body
-                .mesh
-                .iter()
-                .map(|p| {
-                    let t = viewport.translate_pos(p);
-                    [t.x, t.y]
-                })
-                .collect();
-            p.draw(
-                polygon_mesh.as_slice(),
-                &context.draw_state,
-                context.transform,
-                graphics,
-            );
+            particles.draw(&viewport, &context, graphics);
+            if let Some(body) = engine.get_body(lander_body_id) {
+                lander_model.draw(
+                    body.shape(),
+                    &body.transform(),
+                    &commanded_thrust(body, &up),
+                    &viewport,
+                    &context,
+                    graphics,
+                );
+            }
+            let pieces = debris.iter().filter_map(|id| engine.get_body(*id));
+            render::draw_debris(pieces.map(|b| b.mesh()), &viewport, &context, graphics);
+            lander_model.draw_explosion(&viewport, &context, graphics);
             let line = Line::new([0.0, 0.0, 0.0, 1.0], 1.0);
             terrain
                 .iter()
@@ -400,34 +309,24 @@ fn main() {
                 });
         });
 
-        if let Some(button_args) = event.button_args() {
-            let body = engine.get_bodies_mut().first_mut().unwrap();
-            match button_args {
-                ButtonArgs {
-                    state,
-                    button: Button::Keyboard(Key::Down),
-                    ..
-                } => match state {
-                    ButtonState::Press => body.apply_force(0.0, 100.0),
-                    ButtonState::Release => body.apply_force(0.0, -100.0),
-                },
-                ButtonArgs {
-                    state,
-                    button: Button::Keyboard(Key::Right),
-                    ..
-                } => match state {
-                    ButtonState::Press => body.apply_force(-100.0, 0.0),
-                    ButtonState::Release => body.apply_force(100.0, 0.0),
-                },
-                ButtonArgs {
-                    state,
-                    button: Button::Keyboard(Key::Left),
-                    ..
-                } => match state {
-                    ButtonState::Press => body.apply_force(100.0, 0.0),
-                    ButtonState::Release => body.apply_force(-100.0, 0.0),
-                },
-                _ => body.set_resulting_force(0.0, 0.0),
+        if let Some(false) = event.focus_args() {
+            input_map.release_all();
+        }
+
+        for (action, state) in input_map.translate(&event) {
+            match (action, state) {
+                (Action::ThrottleUp, ButtonState::Press) => {
+                    throttle = (throttle + THROTTLE_STEP).min(1.0)
+                }
+                (Action::ThrottleDown, ButtonState::Press) => {
+                    throttle = (throttle - THROTTLE_STEP).max(0.0)
+                }
+                (Action::Pause, ButtonState::Press) => paused = !paused,
+                (Action::Assist, ButtonState::Press) => {
+                    assist = !assist;
+                    println!("autopilot {}", if assist { "on" } else { "off" });
+                }
+                _ => {}
             }
         }
     }
@@ -442,6 +341,7 @@ mod test {
         let vp = ViewPort {
             origin: pos(0.0, 480.0),
             ratio: 1.0,
+            rotation: 0.0,
         };
 
         assert_eq!(vp.translate_pos(&pos(0.0, 0.0)), pos(0.0, 480.0));
@@ -452,6 +352,7 @@ mod test {
         let vp = ViewPort {
             origin: pos(0.0, 480.0),
             ratio: 2.0,
+            rotation: 0.0,
         };
 
         assert_eq!(vp.translate_pos(&pos(0.0, 0.0)), pos(0.0, 240.0));
@@ -462,239 +363,31 @@ mod test {
         let vp = ViewPort {
             origin: pos(0.0, 100.0),
             ratio: 0.10,
+            rotation: 0.0,
         };
 
         assert_eq!(vp.translate_pos(&pos(50.0, 50.0)), pos(500.0, 500.0));
     }
 
     #[test]
-    fn create_still_body() {
-        let mut engine = Engine::create(10.0);
-        engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
-        engine.tick(1.0);
-        {
-            let body = &engine.get_bodies()[0];
-            assert_eq!(body.mesh, [pos(100.0, 95.0)]);
-        }
-        engine.tick(1.0);
-        {
-            let body = &engine.get_bodies()[0];
-            assert_eq!(body.mesh, [pos(100.0, 80.0)]);
-        }
-    }
-
-    #[test]
-    fn free_fall_on_moon() {
-        let mut engine = Engine::create(1.625);
-        engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
-        engine.tick(1.0);
-        {
-            let body = &engine.get_bodies()[0];
-            assert_eq!(body.mesh, [pos(100.0, 99.1875)]);
-        }
-    }
-
-    #[test]
-    fn force_opposite_to_gravity() {
-        let mut engine = Engine::create(10.0);
-        engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
-        engine
-            .get_bodies_mut()
-            .first_mut()
-            .unwrap()
-            .apply_force(0.0, 100.0);
-
-        engine.tick(1.0);
-
-        let body = engine.get_bodies().first().unwrap();
-
-        assert_eq!(body.mesh, [pos(100.0, 100.0)]);
-    }
-
-    #[test]
-    fn set_resulting_force() {
-        let mut engine = Engine::create(10.0);
-        engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
-        engine
-            .get_bodies_mut()
-            .first_mut()
-            .unwrap()
-            .set_resulting_force(0.0, 100.0);
-
-        engine.tick(1.0);
-
-        let body = engine.get_bodies().first().unwrap();
-
-        assert_eq!(body.mesh, [pos(100.0, 100.0)]);
-    }
-
-    #[test]
-    fn forces_on_both_axis() {
-        let mut engine = Engine::create(0.0);
-        engine.add_body(ConvexBody::still_body(10.0, &[pos(100.0, 100.0)]));
-
-        {
-            let body = engine.get_bodies_mut().first_mut().unwrap();
-
-            body.apply_force(100.0, 0.0);
-            body.apply_force(0.0, 100.0);
-        }
-
-        engine.tick(1.0);
-
-        let body = engine.get_bodies_mut().first_mut().unwrap();
-        assert_eq!(body.mesh, [pos(105.0, 105.0)]);
-    }
-
-    #[test]
-    fn point_projection() {
-        assert_eq!(project(&pos(5.0, 0.0), 0.0), pos(5.0, 0.0));
-        assert_eq!(project(&pos(0.0, 5.0), 0.0), pos(0.0, 0.0));
-        assert_eq!(project(&pos(2.0, 0.0), 1.0), pos(1.0, 1.0));
-        assert_eq!(project(&pos(0.0, 2.0), 1.0), pos(1.0, 1.0));
-        assert_eq!(project(&pos(2.0, 0.0), -1.0), pos(1.0, -1.0));
-        assert_eq!(project(&pos(0.0, -2.0), -1.0), pos(1.0, -1.0));
-    }
-
-    #[test]
-    fn collision_two_non_intersecting_triangles() {
-        let triangle1 = positions![(1.0, 1.0), (3.0, 1.0), (2.0, 3.0)];
-        let triangle2 = positions![(3.0, 3.0), (4.0, 1.0), (5.0, 3.0)];
-        assert!(!collided(&triangle1, &triangle2));
-    }
-
-    #[test]
-    fn collision_two_triangles_sharing_one_edge() {
-        let triangle1 = positions![(1.0, 1.0), (3.0, 1.0), (2.0, 3.0)];
-        let triangle2 = positions![(2.0, 3.0), (3.0, 1.0), (4.0, 3.0)];
-        assert!(collided(&triangle1, &triangle2));
-    }
-
-    #[test]
-    fn collision_two_triangles_overlapping() {
-        let triangle1 = positions![(1.0, 1.0), (3.0, 1.0), (2.0, 3.0)];
-        let triangle2 = positions![(2.0, 2.0), (1.0, 4.0), (3.0, 4.0)];
-        assert!(collided(&triangle1, &triangle2));
-    }
-
-    #[test]
-    fn collision_two_rectangles() {
-        let mesh1 = [
-            pos(0.0, 20.0),
-            pos(100.0, 20.0),
-            pos(100.0, 10.0),
-            pos(0.0, 10.0),
-        ];
-
-        let mesh2 = [
-            pos(40.0, 20.0),
-            pos(50.0, 20.0),
-            pos(50.0, 30.0),
-            pos(40.0, 30.0),
-        ];
-
-        assert!(collided(&mesh1, &mesh2));
-    }
-
-    #[test]
-    fn collision_trapezoid_and_rectangle() {
-        let trapezoid = [
-            pos(0.0, 0.0),
-            pos(20.0, 0.0),
-            pos(10.0, 10.0),
-            pos(0.0, 10.0),
-        ];
-
-        let rectangle = [
-            pos(16.0, 5.0),
-            pos(25.0, 5.0),
-            pos(25.0, 15.0),
-            pos(16.0, 15.0),
-        ];
-
-        assert!(!collided(&rectangle, &trapezoid));
-        assert!(!collided(&rectangle, &trapezoid));
-    }
-
-    #[test]
-    fn collision_bug_test() {
-        let m1 = [
-            crate::Position {
-                x: 49.0,
-                y: 15.743524305555098,
-            },
-            crate::Position {
-                x: 51.0,
-                y: 15.743524305555098,
-            },
-            crate::Position {
-                x: 51.0,
-                y: 13.743524305555086,
-            },
-            crate::Position {
-                x: 49.0,
-                y: 13.743524305555086,
-            },
-        ];
-
-        let m2 = [
-            crate::Position {
-                x: 50.0,
-                y: 13.619878363341645,
-            },
-            crate::Position {
-                x: 55.0,
-                y: 14.721771340827875,
-            },
-            crate::Position {
-                x: 55.0,
-                y: 4.721771340827875,
-            },
-            crate::Position {
-                x: 50.0,
-                y: 3.6198783633416447,
-            },
-        ];
-
-        assert!(collided(&m1, &m2));
-    }
+    fn view_port_follows_local_vertical() {
+        let mut vp = ViewPort {
+            origin: pos(0.0, 0.0),
+            ratio: 1.0,
+            rotation: 0.0,
+        };
+        let close = |p: Position, expected: Position| {
+            assert!((p.x - expected.x).abs() < 1e-9 && (p.y - expected.y).abs() < 1e-9);
+        };
 
-    #[test]
-    fn partition_terrain_test() {
-        let terrain = positions![(0.0, 5.0), (1.0, 6.0), (2.0, 4.0), (3.0, 4.0)];
-        let polygons = partition_terrain(&terrain);
-        assert_eq!(
-            polygons.as_slice(),
-            &[
-                TerrainPartition {
-                    safe: false,
-                    mesh: positions![(0.0, 5.0), (1.0, 6.0), (1.0, -4.0), (0.0, -5.0)]
-                },
-                TerrainPartition {
-                    safe: false,
-                    mesh: positions![(1.0, 6.0), (2.0, 4.0), (2.0, -6.0), (1.0, -4.0)]
-                },
-                TerrainPartition {
-                    safe: false,
-                    mesh: positions![(2.0, 4.0), (3.0, 4.0), (3.0, -6.0), (2.0, -6.0)]
-                }
-            ]
-        );
-    }
+        // On the right side of a planet, world x is up on screen.
+        vp.follow(&pos(100.0, 0.0), &v(1.0, 0.0), 10.0, 100.0);
+        close(vp.translate_pos(&pos(100.0, 0.0)), pos(50.0, 50.0));
+        close(vp.translate_pos(&pos(101.0, 0.0)), pos(50.0, 40.0));
+        close(vp.translate_pos(&pos(100.0, -1.0)), pos(60.0, 50.0));
 
-    #[test]
-    fn collision_between_non_fixed_and_fixed_body() {
-        let mut engine = Engine::create(10.0);
-        engine.add_body(ConvexBody::fixed_body(&positions![
-            (0.0, 0.0),
-            (4.0, 0.0),
-            (4.0, -1.0),
-            (0.0, -1.0)
-        ]));
-        engine.add_body(ConvexBody::still_body(
-            10.0,
-            &positions![(1.0, 1.0), (2.0, 1.0), (1.5, 2.0)],
-        ));
-        engine.tick(0.5);
+        vp.follow(&pos(3.0, 4.0), &v(0.0, 1.0), 10.0, 100.0);
+        close(vp.translate_pos(&pos(3.0, 5.0)), pos(50.0, 40.0));
+        close(vp.translate_pos(&pos(4.0, 4.0)), pos(60.0, 50.0));
     }
 }
@@ -0,0 +1,232 @@
+//! 2D vector math: `Vec2` for positions and vectors alike, and
+//! `Transform2` for placing things with a rotation and a translation.
+
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub const fn new(x: f64, y: f64) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    pub fn dot(self, other: Vec2) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// The z of the cross product, positive when `other` is
+    /// counterclockwise from `self`.
+    pub fn cross(self, other: Vec2) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn length(self) -> f64 {
+        self.x.hypot(self.y)
+    }
+
+    /// Same direction with a length of 1, or zero for zero.
+    pub fn normalize(self) -> Vec2 {
+        let length = self.length();
+        if length == 0.0 {
+            Vec2::ZERO
+        } else {
+            self / length
+        }
+    }
+
+    /// Turned by `angle` radians, counterclockwise.
+    pub fn rotate(self, angle: f64) -> Vec2 {
+        let (sin, cos) = angle.sin_cos();
+        Vec2::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+
+    /// Turned by a quarter turn, counterclockwise.
+    pub fn perpendicular(self) -> Vec2 {
+        Vec2::new(-self.y, self.x)
+    }
+}
+
+impl fmt::Display for Vec2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({},{})", self.x, self.y)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f64> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, k: f64) -> Vec2 {
+        Vec2::new(self.x * k, self.y * k)
+    }
+}
+
+impl Mul<Vec2> for f64 {
+    type Output = Vec2;
+
+    fn mul(self, vector: Vec2) -> Vec2 {
+        vector * self
+    }
+}
+
+impl Div<f64> for Vec2 {
+    type Output = Vec2;
+
+    fn div(self, k: f64) -> Vec2 {
+        Vec2::new(self.x / k, self.y / k)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Vec2) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign<f64> for Vec2 {
+    fn mul_assign(&mut self, k: f64) {
+        *self = *self * k;
+    }
+}
+
+impl Sum for Vec2 {
+    fn sum<I: Iterator<Item = Vec2>>(iter: I) -> Vec2 {
+        iter.fold(Vec2::ZERO, Add::add)
+    }
+}
+
+/// Rotation by `rotation` radians, counterclockwise around the origin,
+/// followed by a translation by `translation`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Transform2 {
+    pub translation: Vec2,
+    pub rotation: f64,
+}
+
+impl Transform2 {
+    pub const IDENTITY: Transform2 = Transform2 {
+        translation: Vec2::ZERO,
+        rotation: 0.0,
+    };
+
+    pub fn new(translation: Vec2, rotation: f64) -> Transform2 {
+        Transform2 {
+            translation,
+            rotation,
+        }
+    }
+
+    /// Rotation by `angle` radians, counterclockwise around `center`.
+    pub fn rotation_about(center: Vec2, angle: f64) -> Transform2 {
+        Transform2::new(center - center.rotate(angle), angle)
+    }
+
+    /// Where `point` ends up.
+    pub fn apply(&self, point: Vec2) -> Vec2 {
+        point.rotate(self.rotation) + self.translation
+    }
+
+    /// Where `vector` ends up, which translations don't change.
+    pub fn apply_vector(&self, vector: Vec2) -> Vec2 {
+        vector.rotate(self.rotation)
+    }
+
+    pub fn inverse(&self) -> Transform2 {
+        Transform2::new(-self.translation.rotate(-self.rotation), -self.rotation)
+    }
+
+    /// `self` followed by `other`.
+    pub fn then(&self, other: &Transform2) -> Transform2 {
+        Transform2::new(
+            other.apply(self.translation),
+            self.rotation + other.rotation,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::math::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        (a - b).length() < 1e-12
+    }
+
+    #[test]
+    fn vector_arithmetic() {
+        let (a, b) = (Vec2::new(3.0, 4.0), Vec2::new(1.0, -2.0));
+        assert_eq!(a + b, Vec2::new(4.0, 2.0));
+        assert_eq!(a - b, Vec2::new(2.0, 6.0));
+        assert_eq!(a * 2.0, 2.0 * a);
+        assert_eq!(-a / 2.0, Vec2::new(-1.5, -2.0));
+        assert_eq!(a.dot(b), -5.0);
+        assert_eq!(a.cross(b), -10.0);
+        assert_eq!(a.length(), 5.0);
+        assert_eq!(a.normalize(), Vec2::new(0.6, 0.8));
+        assert_eq!(Vec2::ZERO.normalize(), Vec2::ZERO);
+        assert_eq!(a.perpendicular(), Vec2::new(-4.0, 3.0));
+        assert!(close(a.rotate(FRAC_PI_2), a.perpendicular()));
+        assert_eq!([a, b].into_iter().sum::<Vec2>(), a + b);
+
+        let mut c = a;
+        c += b;
+        c -= a;
+        c *= 3.0;
+        assert_eq!(c, Vec2::new(3.0, -6.0));
+    }
+
+    #[test]
+    fn transforms_compose_and_invert() {
+        let t = Transform2::new(Vec2::new(10.0, 0.0), FRAC_PI_2);
+        assert!(close(t.apply(Vec2::new(1.0, 0.0)), Vec2::new(10.0, 1.0)));
+        assert!(close(
+            t.apply_vector(Vec2::new(1.0, 0.0)),
+            Vec2::new(0.0, 1.0)
+        ));
+
+        let p = Vec2::new(-2.0, 7.0);
+        assert!(close(t.inverse().apply(t.apply(p)), p));
+        let u = Transform2::rotation_about(Vec2::new(1.0, 1.0), 0.3);
+        assert!(close(u.apply(Vec2::new(1.0, 1.0)), Vec2::new(1.0, 1.0)));
+        assert!(close(t.then(&u).apply(p), u.apply(t.apply(p))));
+        assert_eq!(Transform2::IDENTITY.apply(p), p);
+    }
+}
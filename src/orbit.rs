@@ -0,0 +1,264 @@
+//! Descent from orbit around a whole moon. The moon is centered on the
+//! origin and scaled down to a radius of a couple of kilometers, so that
+//! an orbit takes minutes rather than hours, while keeping the Moon's
+//! surface gravity. The lander starts on a low circular orbit and has to
+//! burn retrograde to deorbit before its powered descent.
+
+use crate::gravity::Gravity;
+use crate::{
+    add_terrain, create_lander, pos, v, BodyId, ConvexBody, Engine, Position, TerrainPartition,
+    Vector, MOON_GRAVITY, TERRAIN_DEPTH,
+};
+use itertools::Itertools;
+use rand::Rng;
+use std::f64::consts::PI;
+
+/// Mean radius of the moon, in meters.
+pub const RADIUS: f64 = 2_000.0;
+/// Gravitational parameter giving the Moon's gravity at the surface.
+pub const MU: f64 = MOON_GRAVITY * RADIUS * RADIUS;
+/// Altitude of the starting orbit over the mean radius.
+pub const ORBIT_ALTITUDE: f64 = 150.0;
+
+/// Number of segments of the surface.
+const SEGMENTS: usize = 300;
+/// Number of flat segments to land on.
+const PADS: usize = 6;
+
+pub fn gravity() -> Gravity {
+    Gravity::PointMass {
+        center: pos(0.0, 0.0),
+        mu: MU,
+    }
+}
+
+/// Speed of a circular orbit `r` meters from the center.
+pub fn orbital_speed(r: f64) -> f64 {
+    (MU / r).sqrt()
+}
+
+/// Unit vector pointing up, away from the center, at `position`.
+pub fn local_up(position: &Position) -> Vector {
+    position.normalize()
+}
+
+/// Expresses `vector` in the frame of a lander holding `up` as its
+/// vertical: x to the right of `up`, y along it.
+pub fn to_local(vector: &Vector, up: &Vector) -> Vector {
+    v(vector.cross(*up), vector.dot(*up))
+}
+
+/// Turns `lander` to hold the local vertical at its position.
+pub fn hold_local_vertical(lander: &mut ConvexBody) {
+    let up = local_up(&lander.position());
+    lander.rotate((-up.x).atan2(up.y) - lander.angle());
+    lander.angular_velocity = 0.0;
+}
+
+/// Generates the surface of the moon out of `rng`, as a closed loop of
+/// points going clockwise. Its last point connects back to the first one.
+pub fn generate_surface_with<R: Rng>(rng: &mut R) -> Vec<Position> {
+    let mut elevations = (0..SEGMENTS)
+        .map(|_| rng.gen_range(2.0..20.0))
+        .collect::<Vec<_>>();
+    for _ in 0..PADS {
+        let site = rng.gen_range(0..SEGMENTS);
+        elevations[(site + 1) % SEGMENTS] = elevations[site];
+    }
+    elevations
+        .iter()
+        .enumerate()
+        .map(|(i, elevation)| {
+            let angle = PI / 2.0 - 2.0 * PI * i as f64 / SEGMENTS as f64;
+            let r = RADIUS + elevation;
+            pos(r * angle.cos(), r * angle.sin())
+        })
+        .collect()
+}
+
+/// Same as `partition_terrain`, for a closed `surface` around the
+/// center. Partitions go down to a common radius, `TERRAIN_DEPTH` meters under
+/// the lowest point of `surface`.
+pub fn partition_surface(surface: &[Position]) -> Vec<TerrainPartition> {
+    let lowest = surface
+        .iter()
+        .map(|p| p.length())
+        .fold(f64::INFINITY, f64::min);
+    partition_surface_to(surface, lowest - TERRAIN_DEPTH)
+}
+
+/// Same as `partition_surface`, with partitions going down to
+/// `inner_radius`, which has to be under every point of `surface`.
+pub fn partition_surface_to(surface: &[Position], inner_radius: f64) -> Vec<TerrainPartition> {
+    debug_assert!(
+        surface.iter().all(|p| p.length() > inner_radius),
+        "inner radius {inner_radius} isn't under the surface"
+    );
+    let below = |p: &Position| *p * inner_radius / p.length();
+    surface
+        .iter()
+        .circular_tuple_windows()
+        .map(|(p1, p2)| TerrainPartition {
+            safe: (p1.length() - p2.length()).abs() < 1e-9,
+            mesh: [*p1, *p2, below(p2), below(p1)],
+        })
+        .collect()
+}
+
+pub struct Mission {
+    pub engine: Engine,
+    pub lander: BodyId,
+    pub surface: Vec<Position>,
+    /// Surface bodies with whether they're safe to land on.
+    pub terrain_safety: Vec<(BodyId, bool)>,
+}
+
+impl Mission {
+    /// Creates a mission on a surface generated out of `rng`, with the
+    /// lander on a circular orbit above the top of the moon, going
+    /// clockwise.
+    pub fn create_with<R: Rng>(rng: &mut R) -> Mission {
+        let surface = generate_surface_with(rng);
+        let mut engine = Engine::create_with(gravity());
+        let mut lander = create_lander();
+        let r = RADIUS + ORBIT_ALTITUDE;
+        lander.translate(v(0.0, r) - lander.position());
+        lander.velocity = v(orbital_speed(r), 0.0);
+        let lander = engine.add_body(lander);
+        let terrain_safety = add_terrain(&mut engine, &partition_surface(&surface));
+        Mission {
+            engine,
+            lander,
+            surface,
+            terrain_safety,
+        }
+    }
+
+    pub fn lander(&self) -> &ConvexBody {
+        self.engine.get_body(self.lander).unwrap()
+    }
+
+    /// Altitude of the lander center over the mean radius.
+    pub fn altitude(&self) -> f64 {
+        self.lander().position().length() - RADIUS
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gear::follow_touchdown;
+    use crate::orbit::*;
+    use crate::{apply_thrust_along, Landing, Thrust};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn local_frame() {
+        let up = local_up(&pos(0.0, 10.0));
+        assert_eq!(up, v(0.0, 1.0));
+        assert_eq!(to_local(&v(3.0, -1.0), &up), v(3.0, -1.0));
+
+        // On the right side of the moon, right is down.
+        let up = local_up(&pos(10.0, 0.0));
+        assert_eq!(to_local(&v(0.0, -2.0), &up), v(2.0, 0.0));
+        assert_eq!(to_local(&v(1.0, 0.0), &up), v(0.0, 1.0));
+    }
+
+    #[test]
+    fn lander_holds_local_vertical() {
+        let mut lander = create_lander();
+        lander.translate(v(1000.0, 0.0));
+        hold_local_vertical(&mut lander);
+        let up = local_up(&lander.position());
+        assert!((lander.angle() - (-up.x).atan2(up.y)).abs() < 1e-12);
+        // The legs, at the bottom corners, now point to the center.
+        let (top, bottom) = (&lander.mesh()[0], &lander.mesh()[3]);
+        let down = *bottom - *top;
+        assert!((to_local(&down, &up).y + 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn surface_goes_around_the_moon() {
+        let surface = generate_surface_with(&mut StdRng::seed_from_u64(1));
+        assert_eq!(surface.len(), SEGMENTS);
+        assert!(surface
+            .iter()
+            .all(|p| (RADIUS + 2.0..RADIUS + 20.0).contains(&p.length())));
+
+        let partitions = partition_surface(&surface);
+        assert_eq!(partitions.len(), SEGMENTS);
+        assert!(partitions.iter().any(|p| p.safe));
+        // The last partition closes the loop.
+        assert_eq!(partitions[SEGMENTS - 1].mesh[1], surface[0]);
+    }
+
+    #[test]
+    fn partition_surface_to_a_common_radius() {
+        let surface = generate_surface_with(&mut StdRng::seed_from_u64(1));
+        let lowest = surface
+            .iter()
+            .map(|p| p.length())
+            .fold(f64::INFINITY, f64::min);
+        let partitions = partition_surface(&surface);
+        assert!(partitions.iter().all(|p| {
+            (p.mesh[2].length() - (lowest - TERRAIN_DEPTH)).abs() < 1e-9
+                && (p.mesh[3].length() - (lowest - TERRAIN_DEPTH)).abs() < 1e-9
+        }));
+        assert!(partitions
+            .iter()
+            .all(|p| crate::shape::validate(&p.mesh).is_ok()));
+
+        let partitions = partition_surface_to(&surface, RADIUS - 100.0);
+        assert!(partitions
+            .iter()
+            .all(|p| (p.mesh[2].length() - (RADIUS - 100.0)).abs() < 1e-9));
+    }
+
+    #[test]
+    #[should_panic]
+    fn partition_surface_to_a_radius_above_the_surface() {
+        let surface = generate_surface_with(&mut StdRng::seed_from_u64(1));
+        partition_surface_to(&surface, RADIUS + 10.0);
+    }
+
+    #[test]
+    fn stays_in_orbit_without_thrust() {
+        let mut mission = Mission::create_with(&mut StdRng::seed_from_u64(2));
+        let r = RADIUS + ORBIT_ALTITUDE;
+        let period = 2.0 * PI * r / orbital_speed(r);
+        let dt = 1.0 / 20.0;
+        (0..(period / dt) as usize).for_each(|_| {
+            mission.engine.tick(dt);
+            assert!((mission.altitude() - ORBIT_ALTITUDE).abs() < 0.1);
+        });
+        assert!(!mission.engine.has_collisions());
+        let end = mission.lander().position();
+        assert!(end.x.abs() < 10.0 && end.y > 0.0);
+    }
+
+    #[test]
+    fn retrograde_burn_deorbits() {
+        let mut mission = Mission::create_with(&mut StdRng::seed_from_u64(3));
+        let dt = 1.0 / 20.0;
+        let retrograde = Thrust {
+            main: 0.0,
+            lateral: -1.0,
+        };
+        let mut touchdown = None;
+        let landing = (0..20 * 600).find_map(|step| {
+            let lander = mission.engine.get_body_mut(mission.lander).unwrap();
+            let up = local_up(&lander.position());
+            let thrust = if step < 20 {
+                retrograde.clone()
+            } else {
+                Thrust::default()
+            };
+            apply_thrust_along(lander, &thrust, &up);
+            mission.engine.tick(dt);
+            let terrain = &mission.terrain_safety;
+            follow_touchdown(&mut touchdown, &mission.engine, mission.lander, terrain, dt)
+        });
+        // Falling from orbit without braking doesn't end well.
+        assert_eq!(landing, Some(Landing::Crash));
+    }
+}
@@ -0,0 +1,258 @@
+//! Lightweight particle effects: exhaust plume and dust kicked up from
+//! the terrain. Particles live outside of `Engine` and only collide
+//! against the terrain polyline, so they cost nothing in the narrow phase.
+
+use crate::ViewPort;
+use fma::{pos, v, Position, Vector};
+use itertools::Itertools;
+use piston_window::{rectangle, Context, Graphics};
+use rand::Rng;
+use std::ops::Range;
+
+/// Upper bound on live particles, so a long burn can't grow without limit.
+const MAX_PARTICLES: usize = 2000;
+
+/// How many dust particles an exhaust particle spawns when it
+/// hits the ground.
+const DUST_PER_IMPACT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParticleKind {
+    Exhaust,
+    Dust,
+}
+
+#[derive(Debug)]
+struct Particle {
+    kind: ParticleKind,
+    position: Position,
+    velocity: Vector,
+    age: f64,
+    lifetime: f64,
+    /// Size in meters.
+    size: f64,
+}
+
+impl Particle {
+    fn alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+
+    fn color(&self) -> [f32; 4] {
+        let fade = (1.0 - self.age / self.lifetime).clamp(0.0, 1.0) as f32;
+        match self.kind {
+            ParticleKind::Exhaust => [1.0, 0.9 - 0.5 * (1.0 - fade), 0.3 * fade, fade],
+            ParticleKind::Dust => [0.55, 0.5, 0.45, 0.7 * fade],
+        }
+    }
+}
+
+/// Spawns particles at a point, towards a direction, at a given rate.
+#[derive(Debug)]
+pub struct Emitter {
+    pub kind: ParticleKind,
+    pub position: Position,
+    /// Direction of emission, in radians, counter clockwise from +x.
+    pub direction: f64,
+    /// Maximum deviation from `direction`, in radians.
+    pub spread: f64,
+    /// Particles per second.
+    pub rate: f64,
+    pub speed: Range<f64>,
+    pub lifetime: Range<f64>,
+    /// Velocity of the emitter itself, inherited by the particles.
+    pub velocity: Vector,
+    /// Fraction of a particle carried over from previous updates.
+    pending: f64,
+}
+
+impl Emitter {
+    pub fn create(kind: ParticleKind, speed: Range<f64>, lifetime: Range<f64>) -> Emitter {
+        Emitter {
+            kind,
+            position: pos(0.0, 0.0),
+            direction: 0.0,
+            spread: 0.0,
+            rate: 0.0,
+            speed,
+            lifetime,
+            velocity: Vector::ZERO,
+            pending: 0.0,
+        }
+    }
+
+    /// Returns how many particles are due after `dt` seconds.
+    fn due(&mut self, dt: f64) -> usize {
+        self.pending += self.rate.max(0.0) * dt;
+        let due = self.pending.floor();
+        self.pending -= due;
+        due as usize
+    }
+}
+
+/// Returns the height of `terrain` at `x`, or `None` if `x` is out of
+/// the terrain horizontal limits.
+fn terrain_height(terrain: &[Position], x: f64) -> Option<f64> {
+    terrain
+        .iter()
+        .tuple_windows()
+        .find(|(p1, p2)| p1.x <= x && x <= p2.x)
+        .map(|(p1, p2)| {
+            if p2.x == p1.x {
+                p1.y.max(p2.y)
+            } else {
+                p1.y + (p2.y - p1.y) * (x - p1.x) / (p2.x - p1.x)
+            }
+        })
+}
+
+#[derive(Debug, Default)]
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+}
+
+impl ParticleSystem {
+    pub fn new() -> ParticleSystem {
+        ParticleSystem::default()
+    }
+
+    fn spawn(&mut self, particle: Particle) {
+        if self.particles.len() < MAX_PARTICLES {
+            self.particles.push(particle);
+        }
+    }
+
+    /// Spawns the particles `emitter` is due after `dt` seconds.
+    pub fn emit(&mut self, emitter: &mut Emitter, dt: f64) {
+        let mut rng = rand::thread_rng();
+        for _ in 0..emitter.due(dt) {
+            let angle = emitter.direction + rng.gen_range(-1.0..=1.0) * emitter.spread;
+            let speed = rng.gen_range(emitter.speed.clone());
+            self.spawn(Particle {
+                kind: emitter.kind,
+                position: emitter.position,
+                velocity: emitter.velocity + v(angle.cos(), angle.sin()) * speed,
+                age: 0.0,
+                lifetime: rng.gen_range(emitter.lifetime.clone()),
+                size: rng.gen_range(0.1..0.3),
+            });
+        }
+    }
+
+    /// Moves particles `dt` seconds forward under gravity `ga`. Exhaust
+    /// particles hitting `terrain` turn into dust, dust settles on it.
+    pub fn update(&mut self, dt: f64, ga: f64, terrain: &[Position]) {
+        let mut rng = rand::thread_rng();
+        let mut dust = vec![];
+
+        self.particles.iter_mut().for_each(|p| {
+            p.age += dt;
+            p.velocity.y -= ga * dt;
+            p.position += p.velocity * dt;
+
+            let ground = match terrain_height(terrain, p.position.x) {
+                Some(ground) if p.position.y <= ground => ground,
+                _ => return,
+            };
+            p.position.y = ground;
+            match p.kind {
+                ParticleKind::Exhaust => {
+                    p.age = p.lifetime;
+                    let speed = p.velocity.length();
+                    for _ in 0..DUST_PER_IMPACT {
+                        let direction = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+                        dust.push(Particle {
+                            kind: ParticleKind::Dust,
+                            position: pos(p.position.x, ground + 0.05),
+                            velocity: v(
+                                direction * rng.gen_range(0.2..0.6),
+                                rng.gen_range(0.05..0.3),
+                            ) * speed,
+                            age: 0.0,
+                            lifetime: rng.gen_range(1.0..2.5),
+                            size: rng.gen_range(0.1..0.25),
+                        });
+                    }
+                }
+                ParticleKind::Dust => p.velocity = Vector::ZERO,
+            }
+        });
+
+        self.particles.retain(Particle::alive);
+        dust.into_iter().for_each(|d| self.spawn(d));
+    }
+
+    pub fn draw<G: Graphics>(&self, viewport: &ViewPort, c: &Context, g: &mut G) {
+        self.particles.iter().for_each(|p| {
+            let center = viewport.translate_pos(&p.position);
+            let size = viewport.translate_size(p.size);
+            rectangle(
+                p.color(),
+                [center.x - size / 2.0, center.y - size / 2.0, size, size],
+                c.transform,
+                g,
+            );
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::particles::*;
+    use fma::positions;
+
+    fn exhaust() -> Emitter {
+        Emitter::create(ParticleKind::Exhaust, 5.0..6.0, 10.0..11.0)
+    }
+
+    #[test]
+    fn terrain_height_interpolation() {
+        let terrain = positions![(0.0, 0.0), (10.0, 10.0), (20.0, 10.0)];
+        assert_eq!(terrain_height(&terrain, 5.0), Some(5.0));
+        assert_eq!(terrain_height(&terrain, 15.0), Some(10.0));
+        assert_eq!(terrain_height(&terrain, 25.0), None);
+    }
+
+    #[test]
+    fn emission_rate_carries_over() {
+        let mut system = ParticleSystem::new();
+        let mut emitter = exhaust();
+        emitter.rate = 10.0;
+        system.emit(&mut emitter, 0.15);
+        assert_eq!(system.particles.len(), 1);
+        system.emit(&mut emitter, 0.15);
+        assert_eq!(system.particles.len(), 3);
+    }
+
+    #[test]
+    fn particles_expire() {
+        let mut system = ParticleSystem::new();
+        let mut emitter = exhaust();
+        emitter.rate = 10.0;
+        emitter.direction = std::f64::consts::FRAC_PI_2;
+        system.emit(&mut emitter, 1.0);
+        assert_eq!(system.particles.len(), 10);
+        system.update(9.0, 0.0, &[]);
+        assert_eq!(system.particles.len(), 10);
+        system.update(2.0, 0.0, &[]);
+        assert_eq!(system.particles.len(), 0);
+    }
+
+    #[test]
+    fn exhaust_hitting_terrain_turns_into_dust() {
+        let terrain = positions![(-10.0, 0.0), (10.0, 0.0)];
+        let mut system = ParticleSystem::new();
+        let mut emitter = exhaust();
+        emitter.position = pos(0.0, 1.0);
+        emitter.direction = -std::f64::consts::FRAC_PI_2;
+        emitter.rate = 1.0;
+        system.emit(&mut emitter, 1.0);
+        system.update(0.5, 1.625, &terrain);
+        assert_eq!(system.particles.len(), DUST_PER_IMPACT);
+        assert!(system
+            .particles
+            .iter()
+            .all(|p| p.kind == ParticleKind::Dust));
+        assert!(system.particles.iter().all(|p| p.position.y >= 0.0));
+    }
+}
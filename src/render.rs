@@ -0,0 +1,268 @@
+//! Vector model of the lander: cabin, legs, exhaust flames, and the
+//! explosion and debris shown when a landing goes wrong.
+
+use crate::ViewPort;
+use fma::{bounds, pos, Position, Thrust, Transform2};
+use piston_window::{ellipse, line, polygon, Context, Graphics};
+
+const CABIN_COLOR: [f32; 4] = [0.78, 0.78, 0.80, 1.0];
+const WINDOW_COLOR: [f32; 4] = [0.15, 0.20, 0.35, 1.0];
+const DESCENT_STAGE_COLOR: [f32; 4] = [0.85, 0.65, 0.15, 1.0];
+const NOZZLE_COLOR: [f32; 4] = [0.30, 0.30, 0.30, 1.0];
+const LEG_COLOR: [f32; 4] = [0.20, 0.20, 0.20, 1.0];
+const OUTER_FLAME_COLOR: [f32; 4] = [1.0, 0.45, 0.0, 0.85];
+const INNER_FLAME_COLOR: [f32; 4] = [1.0, 0.9, 0.3, 0.95];
+
+/// How long (in seconds) the explosion stays on screen.
+const EXPLOSION_DURATION: f64 = 2.5;
+
+/// Maps a point in model space, where the bounding box of the
+/// collision shape spans [0, 1] on both axis, onto the body.
+fn model_to_body(min: &Position, max: &Position, x: f64, y: f64) -> Position {
+    pos(min.x + x * (max.x - min.x), min.y + y * (max.y - min.y))
+}
+
+#[derive(Debug)]
+struct Explosion {
+    age: f64,
+    center: Position,
+    /// Radius of the fireball at its peak, in meters.
+    radius: f64,
+}
+
+impl Explosion {
+    fn create(mesh: &[Position]) -> Explosion {
+        let (min, max) = bounds(mesh);
+        Explosion {
+            age: 0.0,
+            center: (min + max) / 2.0,
+            radius: (max.x - min.x).max(max.y - min.y),
+        }
+    }
+
+    fn update(&mut self, dt: f64) {
+        self.age += dt;
+    }
+
+    fn finished(&self) -> bool {
+        self.age >= EXPLOSION_DURATION
+    }
+
+    fn draw<G: Graphics>(&self, viewport: &ViewPort, c: &Context, g: &mut G) {
+        let fade = (1.0 - self.age / EXPLOSION_DURATION).clamp(0.0, 1.0) as f32;
+
+        // The fireball grows quickly and then shrinks while fading.
+        let t = (self.age / EXPLOSION_DURATION).min(1.0);
+        let r = viewport.translate_size(self.radius * (4.0 * t * (1.0 - t) + 0.2));
+        let center = viewport.translate_pos(&self.center);
+        ellipse(
+            [1.0, 0.5, 0.0, 0.8 * fade],
+            [center.x - r, center.y - r, 2.0 * r, 2.0 * r],
+            c.transform,
+            g,
+        );
+        ellipse(
+            [1.0, 0.9, 0.4, 0.9 * fade],
+            [center.x - r / 2.0, center.y - r / 2.0, r, r],
+            c.transform,
+            g,
+        );
+    }
+}
+
+/// Draws the pieces a crashed lander broke into, from their meshes,
+/// in the colors of the lander.
+pub fn draw_debris<'a, G: Graphics>(
+    meshes: impl Iterator<Item = &'a [Position]>,
+    viewport: &ViewPort,
+    c: &Context,
+    g: &mut G,
+) {
+    meshes.enumerate().for_each(|(i, mesh)| {
+        let color = if i % 2 == 0 {
+            CABIN_COLOR
+        } else {
+            DESCENT_STAGE_COLOR
+        };
+        let points: Vec<[f64; 2]> = mesh
+            .iter()
+            .map(|p| {
+                let p = viewport.translate_pos(p);
+                [p.x, p.y]
+            })
+            .collect();
+        polygon(color, points.as_slice(), c.transform, g);
+    });
+}
+
+/// Draws the lander over its collision shape, turned with the body,
+/// animating the flames and the explosion over time.
+#[derive(Debug, Default)]
+pub struct LanderModel {
+    time: f64,
+    explosion: Option<Explosion>,
+}
+
+impl LanderModel {
+    pub fn new() -> LanderModel {
+        LanderModel::default()
+    }
+
+    /// Advances animations by `dt` seconds.
+    pub fn update(&mut self, dt: f64) {
+        self.time += dt;
+        if let Some(explosion) = &mut self.explosion {
+            explosion.update(dt);
+        }
+    }
+
+    /// Blows up the lander occupying `mesh`. Calling it more than once
+    /// has no effect.
+    pub fn explode(&mut self, mesh: &[Position]) {
+        if self.explosion.is_none() {
+            self.explosion = Some(Explosion::create(mesh));
+        }
+    }
+
+    /// Length of the main engine flame, in model units, at the current
+    /// animation time.
+    fn flame_length(&self, throttle: f64) -> f64 {
+        let flicker = 1.0 + 0.15 * (self.time * 45.0).sin() + 0.05 * (self.time * 97.0).sin();
+        throttle.clamp(0.0, 1.0) * 1.2 * flicker
+    }
+
+    /// Draws the explosion, if the lander blew up not long ago.
+    pub fn draw_explosion<G: Graphics>(&self, viewport: &ViewPort, c: &Context, g: &mut G) {
+        if let Some(explosion) = self.explosion.as_ref().filter(|e| !e.finished()) {
+            explosion.draw(viewport, c, g);
+        }
+    }
+
+    /// Draws the lander over its `shape`, in body space, placed in the
+    /// world by `transform`.
+    pub fn draw<G: Graphics>(
+        &self,
+        shape: &[Position],
+        transform: &Transform2,
+        thrust: &Thrust,
+        viewport: &ViewPort,
+        c: &Context,
+        g: &mut G,
+    ) {
+        let (min, max) = bounds(shape);
+        let to_screen = |x: f64, y: f64| {
+            let p = viewport.translate_pos(&transform.apply(model_to_body(&min, &max, x, y)));
+            [p.x, p.y]
+        };
+        let draw_polygon = |color: [f32; 4], points: &[(f64, f64)], g: &mut G| {
+            let points: Vec<[f64; 2]> = points.iter().map(|&(x, y)| to_screen(x, y)).collect();
+            polygon(color, points.as_slice(), c.transform, g);
+        };
+        let draw_line = |color: [f32; 4], (x1, y1): (f64, f64), (x2, y2): (f64, f64), g: &mut G| {
+            let p1 = to_screen(x1, y1);
+            let p2 = to_screen(x2, y2);
+            line(color, 0.5, [p1[0], p1[1], p2[0], p2[1]], c.transform, g);
+        };
+
+        let flame = self.flame_length(thrust.main);
+        if flame > 0.0 {
+            draw_polygon(
+                OUTER_FLAME_COLOR,
+                &[(0.38, 0.1), (0.62, 0.1), (0.5, 0.1 - flame)],
+                g,
+            );
+            draw_polygon(
+                INNER_FLAME_COLOR,
+                &[(0.44, 0.1), (0.56, 0.1), (0.5, 0.1 - flame * 0.6)],
+                g,
+            );
+        }
+
+        // A jet pushing towards +x fires from the left side and vice versa.
+        let jet = self.flame_length(thrust.lateral.abs()) * 0.4;
+        if jet > 0.0 {
+            let (side, direction) = if thrust.lateral > 0.0 {
+                (0.1, -1.0)
+            } else {
+                (0.9, 1.0)
+            };
+            draw_polygon(
+                OUTER_FLAME_COLOR,
+                &[(side, 0.62), (side, 0.72), (side + direction * jet, 0.67)],
+                g,
+            );
+        }
+
+        // Legs and foot pads.
+        draw_line(LEG_COLOR, (0.2, 0.35), (0.02, 0.02), g);
+        draw_line(LEG_COLOR, (0.8, 0.35), (0.98, 0.02), g);
+        draw_line(LEG_COLOR, (0.0, 0.01), (0.1, 0.01), g);
+        draw_line(LEG_COLOR, (0.9, 0.01), (1.0, 0.01), g);
+
+        draw_polygon(
+            NOZZLE_COLOR,
+            &[(0.45, 0.25), (0.55, 0.25), (0.6, 0.1), (0.4, 0.1)],
+            g,
+        );
+        draw_polygon(
+            DESCENT_STAGE_COLOR,
+            &[(0.15, 0.25), (0.85, 0.25), (0.85, 0.5), (0.15, 0.5)],
+            g,
+        );
+        draw_polygon(
+            CABIN_COLOR,
+            &[
+                (0.25, 0.5),
+                (0.75, 0.5),
+                (0.9, 0.65),
+                (0.8, 0.95),
+                (0.2, 0.95),
+                (0.1, 0.65),
+            ],
+            g,
+        );
+        draw_polygon(
+            WINDOW_COLOR,
+            &[(0.3, 0.7), (0.45, 0.7), (0.45, 0.85), (0.3, 0.82)],
+            g,
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::render::*;
+    use fma::positions;
+
+    #[test]
+    fn model_spans_shape_bounding_box() {
+        let mesh = positions![(49.0, 100.0), (51.0, 100.0), (51.0, 98.0), (49.0, 98.0)];
+        let (min, max) = bounds(&mesh);
+        assert_eq!(min, pos(49.0, 98.0));
+        assert_eq!(max, pos(51.0, 100.0));
+        assert_eq!(model_to_body(&min, &max, 0.0, 0.0), pos(49.0, 98.0));
+        assert_eq!(model_to_body(&min, &max, 0.5, 1.0), pos(50.0, 100.0));
+    }
+
+    #[test]
+    fn flame_scales_with_throttle() {
+        let model = LanderModel::new();
+        assert_eq!(model.flame_length(0.0), 0.0);
+        assert!(model.flame_length(1.0) > model.flame_length(0.5));
+        assert_eq!(model.flame_length(2.0), model.flame_length(1.0));
+    }
+
+    #[test]
+    fn explosion_happens_only_once() {
+        let mesh = positions![(0.0, 2.0), (2.0, 2.0), (2.0, 0.0), (0.0, 0.0)];
+        let mut model = LanderModel::new();
+        assert!(model.explosion.is_none());
+        model.explode(&mesh);
+        model.update(1.0);
+        model.explode(&mesh);
+        assert!(model.explosion.is_some());
+        assert_eq!(model.explosion.as_ref().unwrap().age, 1.0);
+        model.update(EXPLOSION_DURATION);
+        assert!(model.explosion.as_ref().unwrap().finished());
+    }
+}
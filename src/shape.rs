@@ -0,0 +1,386 @@
+//! Convex polygons bodies are made of: checking meshes drawn by hand,
+//! building them out of point clouds, and their mass properties.
+//! `ConvexBody::still_body` and `fixed_body` take meshes as they are,
+//! which is only right for convex ones; `validate` tells them apart, and
+//! `ConvexBody::from_mesh` checks meshes with it.
+
+use crate::{bounds, centroid, Position};
+use itertools::Itertools;
+use std::f64::consts::PI;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShapeError {
+    /// A polygon needs at least 3 vertices, the mesh had that many.
+    TooFewVertices(usize),
+    /// The vertex at that index has a NaN or infinite coordinate.
+    NotFinite(usize),
+    /// The vertex at that index is the same as the next one.
+    RepeatedVertex(usize),
+    /// All the vertices are on one line.
+    Degenerate,
+    /// Two edges of the mesh cross each other.
+    SelfIntersecting,
+    /// The mesh turns the other way at the vertex at that index.
+    Concave(usize),
+    /// The mesh goes back along itself at the vertex at that index.
+    DoublesBack(usize),
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShapeError::TooFewVertices(n) => {
+                write!(f, "a shape needs at least 3 vertices, got {}", n)
+            }
+            ShapeError::NotFinite(i) => write!(f, "vertex {} isn't a finite position", i),
+            ShapeError::RepeatedVertex(i) => {
+                write!(f, "vertex {} is the same as the next one", i)
+            }
+            ShapeError::Degenerate => write!(f, "all vertices are on one line"),
+            ShapeError::SelfIntersecting => write!(f, "edges cross each other"),
+            ShapeError::Concave(i) => write!(f, "shape is concave at vertex {}", i),
+            ShapeError::DoublesBack(i) => write!(f, "shape goes back along itself at vertex {}", i),
+        }
+    }
+}
+
+/// Area of `mesh`, positive if it goes around counterclockwise.
+pub fn signed_area(mesh: &[Position]) -> f64 {
+    mesh.iter()
+        .circular_tuple_windows()
+        .map(|(p1, p2)| p1.cross(*p2))
+        .sum::<f64>()
+        / 2.0
+}
+
+pub fn area(mesh: &[Position]) -> f64 {
+    signed_area(mesh).abs()
+}
+
+/// Center of mass of `mesh` taken as a uniform polygon. Meshes without
+/// an area, like single points, fall back to the mean of their vertices.
+pub fn center_of_mass(mesh: &[Position]) -> Position {
+    let Some(&origin) = mesh.first() else {
+        return Position::ZERO;
+    };
+    // Taken from the first vertex, which keeps far away meshes precise.
+    let (area, moment) = mesh.iter().circular_tuple_windows().fold(
+        (0.0, Position::ZERO),
+        |(area, moment), (p1, p2)| {
+            let (r1, r2) = (*p1 - origin, *p2 - origin);
+            let cross = r1.cross(r2);
+            (area + cross, moment + (r1 + r2) * cross)
+        },
+    );
+    if area == 0.0 {
+        centroid(mesh)
+    } else {
+        origin + moment / (3.0 * area)
+    }
+}
+
+/// Moment of inertia of `mesh`, taken as a uniform polygon of 1 kg,
+/// around the origin of its coordinates, in kg·m².
+pub fn unit_inertia(mesh: &[Position]) -> f64 {
+    let (area, second_moment) =
+        mesh.iter()
+            .circular_tuple_windows()
+            .fold((0.0, 0.0), |(area, second_moment), (p1, p2)| {
+                let cross = p1.cross(*p2);
+                (
+                    area + cross,
+                    second_moment + cross * (p1.dot(*p1) + p1.dot(*p2) + p2.dot(*p2)),
+                )
+            });
+    if area == 0.0 {
+        0.0
+    } else {
+        second_moment / (6.0 * area)
+    }
+}
+
+/// Area of the circle, capsule or segment made of `core` grown by
+/// `radius`, or of the polygon `core` for larger ones.
+pub fn rounded_area(core: &[Position], radius: f64) -> f64 {
+    match *core {
+        [_] => PI * radius * radius,
+        [a, b] => 2.0 * radius * (b - a).length() + PI * radius * radius,
+        _ => area(core),
+    }
+}
+
+/// Moment of inertia of the circle, capsule or segment made of `core`
+/// grown by `radius`, of 1 kg, around the origin of its coordinates.
+/// Larger cores are polygons, as `unit_inertia` takes them.
+pub fn rounded_unit_inertia(core: &[Position], radius: f64) -> f64 {
+    match *core {
+        [center] => radius * radius / 2.0 + center.dot(center),
+        [a, b] => {
+            let center = (a + b) / 2.0;
+            let length = (b - a).length();
+            let around_center = if radius == 0.0 {
+                length * length / 12.0
+            } else {
+                // A rectangle with half a disc at each end, the centroid
+                // of each half `offset` away from the rectangle.
+                let rectangle = 2.0 * radius * length;
+                let disc = PI * radius * radius;
+                let offset = 4.0 * radius / (3.0 * PI);
+                (rectangle * (length * length + 4.0 * radius * radius) / 12.0
+                    + disc * (radius * radius / 2.0 + length * length / 4.0 + length * offset))
+                    / (rectangle + disc)
+            };
+            around_center + center.dot(center)
+        }
+        _ => unit_inertia(core),
+    }
+}
+
+/// Whether segments `a1`-`a2` and `b1`-`b2` cross at a point inside both.
+fn segments_cross(a1: Position, a2: Position, b1: Position, b2: Position) -> bool {
+    let (a, b) = (a2 - a1, b2 - b1);
+    let sides_of_b = (a.cross(b1 - a1), a.cross(b2 - a1));
+    let sides_of_a = (b.cross(a1 - b1), b.cross(a2 - b1));
+    sides_of_b.0 * sides_of_b.1 < 0.0 && sides_of_a.0 * sides_of_a.1 < 0.0
+}
+
+fn self_intersecting(mesh: &[Position]) -> bool {
+    let n = mesh.len();
+    (0..n).tuple_combinations().any(|(i, j)| {
+        // Neighbouring edges share a vertex and can't cross.
+        j != i + 1
+            && (j + 1) % n != i
+            && segments_cross(mesh[i], mesh[(i + 1) % n], mesh[j], mesh[(j + 1) % n])
+    })
+}
+
+/// Checks that `mesh` is a convex polygon, and returns its vertices going
+/// around counterclockwise. Vertices on a straight edge are allowed.
+pub fn validate(mesh: &[Position]) -> Result<Vec<Position>, ShapeError> {
+    if mesh.len() < 3 {
+        return Err(ShapeError::TooFewVertices(mesh.len()));
+    }
+    if let Some(i) = mesh
+        .iter()
+        .position(|p| !p.x.is_finite() || !p.y.is_finite())
+    {
+        return Err(ShapeError::NotFinite(i));
+    }
+    if let Some(i) = (0..mesh.len()).find(|&i| mesh[i] == mesh[(i + 1) % mesh.len()]) {
+        return Err(ShapeError::RepeatedVertex(i));
+    }
+    if self_intersecting(mesh) {
+        return Err(ShapeError::SelfIntersecting);
+    }
+    // Turns below this are taken as going straight on.
+    let (min, max) = bounds(mesh);
+    let size = (max - min).x.max((max - min).y);
+    let tolerance = 1e-12 * size * size;
+    let orientation = signed_area(mesh);
+    if orientation.abs() <= tolerance {
+        return Err(ShapeError::Degenerate);
+    }
+    // Going straight on is fine, going straight back isn't.
+    if let Some(i) = mesh
+        .iter()
+        .circular_tuple_windows()
+        .map(|(p1, p2, p3)| (*p2 - *p1, *p3 - *p2))
+        .position(|(e1, e2)| e1.cross(e2).abs() <= tolerance && e1.dot(e2) < 0.0)
+    {
+        return Err(ShapeError::DoublesBack((i + 1) % mesh.len()));
+    }
+    if let Some(i) = mesh
+        .iter()
+        .circular_tuple_windows()
+        .map(|(p1, p2, p3)| (*p2 - *p1).cross(*p3 - *p2) * orientation.signum())
+        .position(|turn| turn < -tolerance)
+    {
+        return Err(ShapeError::Concave((i + 1) % mesh.len()));
+    }
+    let mut mesh = mesh.to_vec();
+    if orientation < 0.0 {
+        mesh.reverse();
+    }
+    Ok(mesh)
+}
+
+/// Pushes `p` on `hull`, first popping the vertices `hull` wouldn't turn
+/// left at anymore, as long as it keeps at least `floor - 1` of them.
+fn push_turning_left(hull: &mut Vec<Position>, p: Position, floor: usize) {
+    while hull.len() >= floor {
+        let (a, b) = (hull[hull.len() - 2], hull[hull.len() - 1]);
+        if (b - a).cross(p - b) > 0.0 {
+            break;
+        }
+        hull.pop();
+    }
+    hull.push(p);
+}
+
+/// Smallest convex polygon holding all of `points`, going around
+/// counterclockwise from its bottom left vertex, without vertices on
+/// straight edges.
+pub fn convex_hull(points: &[Position]) -> Result<Vec<Position>, ShapeError> {
+    if let Some(i) = points
+        .iter()
+        .position(|p| !p.x.is_finite() || !p.y.is_finite())
+    {
+        return Err(ShapeError::NotFinite(i));
+    }
+    if points.len() < 3 {
+        return Err(ShapeError::TooFewVertices(points.len()));
+    }
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    // Andrew's monotone chain: the lower half from left to right, then
+    // the upper half back, each dropping the vertices it turns right at.
+    let mut hull: Vec<Position> = Vec::with_capacity(sorted.len() + 1);
+    sorted
+        .iter()
+        .for_each(|&p| push_turning_left(&mut hull, p, 2));
+    let floor = hull.len() + 1;
+    sorted
+        .iter()
+        .rev()
+        .skip(1)
+        .for_each(|&p| push_turning_left(&mut hull, p, floor));
+    hull.pop();
+    if hull.len() < 3 {
+        return Err(ShapeError::Degenerate);
+    }
+    Ok(hull)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pos;
+    use crate::shape::*;
+
+    const SQUARE: [Position; 4] = positions![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+
+    #[test]
+    fn mass_properties() {
+        assert_eq!(signed_area(&SQUARE), 1.0);
+        let mut clockwise = SQUARE;
+        clockwise.reverse();
+        assert_eq!(signed_area(&clockwise), -1.0);
+        assert_eq!(area(&clockwise), 1.0);
+
+        let triangle = positions![(0.0, 0.0), (3.0, 0.0), (0.0, 3.0)];
+        assert_eq!(center_of_mass(&triangle), pos(1.0, 1.0));
+        assert_eq!(center_of_mass(&[pos(2.0, 5.0)]), pos(2.0, 5.0));
+
+        let centered = SQUARE.map(|p| p - pos(0.5, 0.5));
+        assert!((unit_inertia(&centered) - 1.0 / 6.0).abs() < 1e-12);
+        let mut reversed = centered;
+        reversed.reverse();
+        assert!((unit_inertia(&reversed) - 1.0 / 6.0).abs() < 1e-12);
+
+        assert_eq!(rounded_unit_inertia(&[pos(0.0, 2.0)], 2.0), 6.0);
+        let ends = [pos(-1.5, 0.0), pos(1.5, 0.0)];
+        assert_eq!(rounded_unit_inertia(&ends, 0.0), 0.75);
+        let dot = [pos(0.0, 0.0), pos(0.0, 0.0)];
+        assert!((rounded_unit_inertia(&dot, 2.0) - 2.0).abs() < 1e-12);
+        // Between the segment it's grown from and its bounding box.
+        let capsule = rounded_unit_inertia(&ends, 0.5);
+        let bounding_box = positions![(-2.0, -0.5), (2.0, -0.5), (2.0, 0.5), (-2.0, 0.5)];
+        assert!(capsule > 0.75 && capsule < unit_inertia(&bounding_box));
+        assert_eq!(rounded_unit_inertia(&SQUARE, 1.0), unit_inertia(&SQUARE));
+
+        assert_eq!(rounded_area(&[pos(3.0, 0.0)], 1.0), PI);
+        assert_eq!(rounded_area(&ends, 0.5), 3.0 + PI / 4.0);
+        assert_eq!(rounded_area(&SQUARE, 1.0), 1.0);
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(validate(&SQUARE), Ok(SQUARE.to_vec()));
+        let mut clockwise = SQUARE;
+        clockwise.reverse();
+        assert_eq!(signed_area(&validate(&clockwise).unwrap()), 1.0);
+        // A vertex in the middle of an edge is fine.
+        assert!(validate(&positions![(0.0, 0.0), (0.5, 0.0), (1.0, 0.0), (0.0, 1.0)]).is_ok());
+
+        assert_eq!(
+            validate(&[pos(0.0, 0.0)]),
+            Err(ShapeError::TooFewVertices(1))
+        );
+        assert_eq!(
+            validate(&positions![(0.0, 0.0), (f64::NAN, 0.0), (0.0, 1.0)]),
+            Err(ShapeError::NotFinite(1))
+        );
+        assert_eq!(
+            validate(&positions![(0.0, 0.0), (1.0, 0.0), (1.0, 0.0), (0.0, 1.0)]),
+            Err(ShapeError::RepeatedVertex(1))
+        );
+        assert_eq!(
+            validate(&positions![(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)]),
+            Err(ShapeError::Degenerate)
+        );
+        assert_eq!(
+            validate(&positions![(0.0, 0.0), (1.0, 1.0), (1.0, 0.0), (0.0, 1.0)]),
+            Err(ShapeError::SelfIntersecting)
+        );
+        assert_eq!(
+            validate(&positions![
+                (0.0, 0.0),
+                (2.0, 0.0),
+                (1.0, 0.5),
+                (2.0, 2.0),
+                (0.0, 2.0)
+            ]),
+            Err(ShapeError::Concave(2))
+        );
+        let star = positions![
+            (0.0, 1.0),
+            (0.59, -0.81),
+            (-0.95, 0.31),
+            (0.95, 0.31),
+            (-0.59, -0.81)
+        ];
+        assert_eq!(validate(&star), Err(ShapeError::SelfIntersecting));
+        assert_eq!(
+            validate(&positions![(0.0, 0.0), (2.0, 0.0), (1.0, 0.0), (0.0, 1.0)]),
+            Err(ShapeError::DoublesBack(1))
+        );
+        // Zigzagging along an edge, without turning right anywhere.
+        assert_eq!(
+            validate(&positions![
+                (0.0, 0.0),
+                (2.0, 0.0),
+                (1.0, 0.0),
+                (3.0, 0.0),
+                (0.0, 3.0)
+            ]),
+            Err(ShapeError::DoublesBack(1))
+        );
+    }
+
+    #[test]
+    fn hull_of_a_point_cloud() {
+        let cloud = positions![
+            (1.0, 1.0),
+            (0.0, 2.0),
+            (2.0, 2.0),
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.0),
+            (0.5, 1.5),
+            (0.0, 2.0)
+        ];
+        assert_eq!(
+            convex_hull(&cloud),
+            Ok(positions![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)].to_vec())
+        );
+        assert_eq!(
+            convex_hull(&positions![(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (1.0, 1.0)]),
+            Err(ShapeError::Degenerate)
+        );
+        assert_eq!(
+            convex_hull(&[pos(0.0, 0.0), pos(1.0, 0.0)]),
+            Err(ShapeError::TooFewVertices(2))
+        );
+    }
+}
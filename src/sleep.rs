@@ -0,0 +1,267 @@
+//! Sleeping: bodies resting on others, slow enough for long enough, are left
+//! out of integration and of the search for contacts until something wakes
+//! them. Bodies touching or jointed to each other form islands, which fall
+//! asleep and wake up together, so a body can't stay asleep on top of one
+//! that moves away.
+
+use crate::gear::moment_of_inertia;
+use crate::{ContactPhase, ConvexBody, Engine, Slot};
+
+/// Default `Engine::sleep_energy`, in J/kg, about 4.5 cm/s.
+pub const SLEEP_ENERGY: f64 = 1e-3;
+/// How long bodies have to stay slow before falling asleep, in seconds.
+pub const SLEEP_TIME: f64 = 0.5;
+
+/// Kinetic energy of `body` per kilogram, in J/kg.
+pub fn specific_kinetic_energy(body: &ConvexBody) -> f64 {
+    let spin = if body.angular_velocity == 0.0 {
+        0.0
+    } else {
+        moment_of_inertia(body) / body.mass * body.angular_velocity.powi(2)
+    };
+    0.5 * (body.velocity.dot(body.velocity) + spin)
+}
+
+/// Buffers for telling islands apart, kept from one tick to the next.
+#[derive(Debug, Default)]
+pub(crate) struct Islands {
+    /// Slot index of another body of the same island, or of the body
+    /// itself for the one standing for the island.
+    parents: Vec<usize>,
+    /// Whether the body of each slot rests on something.
+    supported: Vec<bool>,
+    /// Whether the island a slot stands for has a body that can't sleep.
+    restless: Vec<bool>,
+}
+
+impl Islands {
+    fn reset(&mut self, len: usize) {
+        self.parents.clear();
+        self.parents.extend(0..len);
+        self.supported.clear();
+        self.supported.resize(len, false);
+        self.restless.clear();
+        self.restless.resize(len, false);
+    }
+
+    /// Index of the body standing for the island of `index`.
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a.max(b)] = a.min(b);
+    }
+}
+
+fn dynamic(slots: &[Slot], index: usize) -> bool {
+    slots[index].body.as_ref().is_some_and(|body| !body.fixed)
+}
+
+impl Engine {
+    /// Puts to sleep islands whose bodies have all been slow for
+    /// `SLEEP_TIME`, and wakes up the others.
+    pub(crate) fn update_sleep(&mut self, dt: f64) {
+        let mut islands = std::mem::take(&mut self.islands);
+        islands.reset(self.slots.len());
+        // Contacts starting or ending wake their bodies up.
+        for event in &self.events {
+            if event.phase != ContactPhase::Persist && !event.sensor {
+                for id in [event.bodies.0, event.bodies.1] {
+                    if let Some(body) = self.slots[id.index as usize].body.as_mut() {
+                        if !body.fixed {
+                            body.wake();
+                        }
+                    }
+                }
+            }
+        }
+        let links = self.collisions.iter().copied().chain(
+            self.joints
+                .iter()
+                .filter(|joint| {
+                    self.get_body(joint.bodies.0).is_some()
+                        && self.get_body(joint.bodies.1).is_some()
+                })
+                .map(|joint| joint.bodies),
+        );
+        for (a, b) in links {
+            let (a, b) = (a.index as usize, b.index as usize);
+            islands.supported[a] = true;
+            islands.supported[b] = true;
+            if dynamic(&self.slots, a) && dynamic(&self.slots, b) {
+                islands.join(a, b);
+            }
+        }
+
+        let sleep_energy = self.sleep_energy;
+        for index in 0..self.slots.len() {
+            let Some(body) = self.slots[index].body.as_mut().filter(|b| b.moving()) else {
+                continue;
+            };
+            let supported = islands.supported[index] || body.legs.iter().any(|leg| leg.load > 0.0);
+            body.idle = if supported && specific_kinetic_energy(body) < sleep_energy {
+                body.idle + dt
+            } else {
+                0.0
+            };
+            if body.idle < SLEEP_TIME {
+                let island = islands.find(index);
+                islands.restless[island] = true;
+            }
+        }
+        for index in 0..self.slots.len() {
+            if !dynamic(&self.slots, index) {
+                continue;
+            }
+            let island = islands.find(index);
+            let restless = islands.restless[island];
+            let Some(body) = self.slots[index].body.as_mut() else {
+                continue;
+            };
+            if restless {
+                if body.sleeping {
+                    body.wake();
+                }
+            } else if !body.sleeping {
+                body.sleeping = true;
+                body.velocity = crate::Vector::ZERO;
+                body.angular_velocity = 0.0;
+            }
+        }
+        self.islands = islands;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sleep::*;
+    use crate::{create_lander, pos, square, v, BodyId, Position, MOON_GRAVITY};
+
+    fn run(engine: &mut Engine, seconds: f64) {
+        (0..(seconds * 60.0) as usize).for_each(|_| engine.tick(1.0 / 60.0));
+    }
+
+    fn asleep(engine: &Engine, id: BodyId) -> bool {
+        engine.get_body(id).unwrap().sleeping
+    }
+
+    #[test]
+    fn resting_bodies_fall_asleep() {
+        let mut engine = Engine::create(0.0);
+        let a = engine.add_body(square(1.0, pos(0.5, 0.5), 1.0));
+        let b = engine.add_body(square(1.0, pos(1.0, 1.0), 1.0));
+        let alone = engine.add_body(square(1.0, pos(10.5, 0.5), 1.0));
+        run(&mut engine, SLEEP_TIME - 0.1);
+        assert!(!asleep(&engine, a));
+        run(&mut engine, 0.2);
+        assert!(asleep(&engine, a) && asleep(&engine, b));
+        // Nothing holds it up, it's only between two pushes.
+        assert!(!asleep(&engine, alone));
+
+        // The contact between sleeping bodies still holds.
+        run(&mut engine, 1.0);
+        assert_eq!(engine.collisions, [(a, b)]);
+        assert!(engine
+            .contact_events()
+            .iter()
+            .all(|e| e.phase == ContactPhase::Persist));
+    }
+
+    #[test]
+    fn contact_wakes_the_whole_island() {
+        let mut engine = Engine::create(0.0);
+        let a = engine.add_body(square(1.0, pos(0.5, 0.5), 1.0));
+        let b = engine.add_body(square(1.0, pos(1.0, 1.0), 1.0));
+        run(&mut engine, 1.0);
+        assert!(asleep(&engine, a) && asleep(&engine, b));
+
+        let mut bullet = square(1.0, pos(5.5, 0.5), 1.0);
+        bullet.velocity = v(-6.0, 0.0);
+        engine.add_body(bullet);
+        // Only `b` is hit, `a` wakes up with it.
+        run(&mut engine, 0.62);
+        assert_eq!(engine.collisions.len(), 2);
+        assert!(!asleep(&engine, a) && !asleep(&engine, b));
+    }
+
+    #[test]
+    fn forces_wake_bodies() {
+        let mut engine = Engine::create(0.0);
+        let a = engine.add_body(square(1.0, pos(0.5, 0.5), 1.0));
+        engine.add_body(square(1.0, pos(1.0, 1.0), 1.0));
+        run(&mut engine, 1.0);
+        let body = engine.get_body_mut(a).unwrap();
+        body.set_resulting_force(0.0, 0.0);
+        assert!(body.sleeping);
+        body.apply_force(1.0, 0.0);
+        assert!(!body.sleeping);
+        engine.tick(1.0 / 60.0);
+        assert!(engine.get_body(a).unwrap().velocity.x > 0.0);
+    }
+
+    #[test]
+    fn removing_a_support_wakes_bodies() {
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let mut ground = square(1.0, pos(0.5, 0.5), 1.0);
+        ground.fixed = true;
+        let ground = engine.add_body(ground);
+        let anchor = engine.add_body(square(1.0, pos(0.5, 5.5), 1.0));
+        engine.get_body_mut(anchor).unwrap().fixed = true;
+        let body = engine.add_body(square(1.0, pos(1.0, 1.0), 1.0));
+        engine.add_joint(crate::joint::Joint::create(
+            (anchor, body),
+            (v(0.0, -4.5), v(0.0, 0.0)),
+            crate::joint::JointKind::Revolute,
+        ));
+        run(&mut engine, 1.0);
+        assert!(asleep(&engine, body));
+        engine.remove_body(ground);
+        assert!(!asleep(&engine, body));
+    }
+
+    #[test]
+    fn added_bodies_wake_what_they_overlap() {
+        let mut engine = Engine::create(0.0);
+        let a = engine.add_body(square(1.0, pos(0.5, 0.5), 1.0));
+        let b = engine.add_body(square(1.0, pos(1.0, 1.0), 1.0));
+        run(&mut engine, 1.0);
+        assert!(asleep(&engine, a) && asleep(&engine, b));
+
+        let zone = engine.add_body(ConvexBody {
+            sensor: true,
+            fixed: true,
+            ..square(1.0, pos(-0.3, -0.3), 1.0)
+        });
+        let mut wall = square(1.0, pos(1.7, 1.7), 1.0);
+        wall.fixed = true;
+        let wall = engine.add_body(wall);
+        assert!(!asleep(&engine, a) && !asleep(&engine, b));
+        engine.tick(1.0 / 60.0);
+        assert_eq!(engine.overlaps, [(a, zone)]);
+        assert_eq!(engine.collisions, [(a, b), (b, wall)]);
+    }
+
+    #[test]
+    fn landed_lander_costs_nothing() {
+        let mut engine = Engine::create(MOON_GRAVITY);
+        let lander = engine.add_body(create_lander());
+        engine.add_body(ConvexBody::fixed_body(&[
+            pos(0.0, 97.99),
+            pos(100.0, 97.99),
+            pos(100.0, 87.99),
+            pos(0.0, 87.99),
+        ]));
+        run(&mut engine, 10.0);
+        assert!(asleep(&engine, lander));
+        let mesh: Vec<Position> = engine.get_body(lander).unwrap().mesh().to_vec();
+        run(&mut engine, 10.0);
+        assert_eq!(engine.get_body(lander).unwrap().mesh(), mesh);
+        assert!(engine.has_collisions());
+    }
+}